@@ -1,12 +1,28 @@
+use std::cell::RefCell;
 use std::fmt::Display;
+use std::io::IsTerminal;
+use std::path::Path;
 use std::str::FromStr;
 
 use crate::error::{EnumParseError, Error, Result};
-use crate::task::manager::TaskManager;
+use crate::source::migrate::{self, DatasourceSpec};
+use crate::source::{self, Datasource};
+use crate::task::chart;
+use crate::task::date;
+use crate::task::estimate::Estimate;
+use crate::task::filter::{self, Filter};
+use crate::task::manager::{Placement, Subtasks, TaskManager};
+use crate::task::merge::{self, MergePolicy, SyncReport};
+use crate::task::recur::Recurrence;
+use crate::task::review::{self, Action, Summary, Threshold};
+use crate::task::sort::SortKey;
+use crate::task::time::{self, Timesheet};
 use crate::task::{Task, TaskPriority, TaskStatus};
+use chrono::{Datelike, Duration, Local, Utc};
+use clap::builder::RangedU64ValueParser;
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use home;
-use inquire::{Confirm, Editor, Select, Text};
+use inquire::{Confirm, DateSelect, Editor, MultiSelect, Select, Text};
 
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
@@ -20,23 +36,21 @@ pub struct SqlLiteSettings {
     pub path: String,
 }
 
-pub fn default_sqllite_settings() -> SqlLiteSettings {
-    let home_dir = match home::home_dir() {
-        Some(path) => path,
-        None => {
-            panic!(
-                "I am not able to find out what your home directory is. Use the --path flag to set"
-            );
-        }
-    };
-    SqlLiteSettings {
+/// Where the tasks are kept unless `--path` says otherwise: a database in the home directory.
+pub fn default_sqllite_settings() -> Result<SqlLiteSettings> {
+    let home_dir = home::home_dir().ok_or_else(|| {
+        Error::InvalidInput(
+            "can't find your home directory, use --path <file> to say where to keep the tasks"
+                .to_string(),
+        )
+    })?;
+    Ok(SqlLiteSettings {
         path: home_dir
             .join(TASKS_DB_FILE_NAME)
-            .into_os_string()
-            .into_string()
-            .unwrap(),
+            .to_string_lossy()
+            .to_string(),
         datasource: Datasources::SqlLite,
-    }
+    })
 }
 
 /// Search for a pattern in a file and display the lines that contain it.
@@ -50,37 +64,195 @@ pub struct App {
     pub datasource: Option<Datasources>,
     #[arg(short, long)]
     pub filter: bool,
+    /// The datasource file, $HOME/mytasks.sql by default
+    #[arg(short, long)]
+    pub path: Option<String>,
+    /// Commit every change to a git repository next to the database file
+    #[arg(short, long)]
+    pub git: bool,
+    /// Number open tasks 1..N in list and accept these numbers as task ids, use #<id> for stable ids
+    #[arg(short, long)]
+    pub working_ids: bool,
 }
 
 impl App {
-    pub fn run_prompt(&self, manager: &mut TaskManager) {
+    /// Runs the command, the error says why it failed and how the process should exit.
+    pub fn run_prompt(&self, manager: &mut TaskManager) -> Result<()> {
         match &self.command {
-            Command::List => {
+            Command::List { actionable, sort } => {
                 let mut filter: Option<(FilterOptions, String)> = None;
                 if self.filter {
-                    filter = Some(self.run_list_command().unwrap());
+                    filter = Some(self.run_list_command()?);
+                }
+                if *actionable {
+                    manager.list_actionable(&filter, sort)?;
+                } else {
+                    manager.list(&filter, sort)?;
                 }
-                manager.list(&filter);
             }
-            Command::Add => match manager.add(self.run_add_command().unwrap()) {
-                Ok(_) => log::info!("added task successfully"),
-                Err(e) => log::error!("failed to add task: {e:?}"),
-            },
-            Command::Show => match manager.show(self.ask_task_id().unwrap()) {
-                Ok(_) => log::info!("show task run successfully"),
-                Err(e) => log::error!("failed to show task: {e:?}"),
-            },
-            Command::Remove => match manager.remove(self.run_remove_command().unwrap()) {
-                Ok(_) => log::info!("removed task successfully"),
-                Err(e) => log::error!("failed to remove task: {e:?}"),
-            },
-            Command::SetStatus => match self.set_status_command(manager) {
-                Ok(_) => log::info!("task updated successfully"),
-                Err(e) => log::error!("failed to update task: {e:?}"),
+            Command::Next { n } => {
+                let suggestions = manager.next(*n)?;
+                if suggestions.is_empty() {
+                    println!("Nothing to do right now.");
+                }
+                for (i, s) in suggestions.iter().enumerate() {
+                    println!("{}. #{} {}", i + 1, s.task.id.unwrap(), s.task.short);
+                    println!("   {}", s.reasons.join(", "));
+                }
+            }
+            Command::Move {
+                task,
+                before,
+                after,
+                top,
+                bottom,
+            } => {
+                self.move_command(manager, task, before, after, *top, *bottom)?;
+                log::info!("task moved successfully");
+            }
+            Command::Reorder => {
+                self.reorder_command(manager)?;
+                log::info!("tasks reordered successfully");
+            }
+            Command::Review { weekly: true, .. } => {
+                print!("{}", self.weekly_review_command(manager)?)
+            }
+            Command::Review {
+                threshold,
+                since_created,
+                save,
+                ..
+            } => {
+                if *save {
+                    manager.save_review_thresholds(threshold)?;
+                }
+                let n = self.review_command(manager, threshold, *since_created)?;
+                println!("reviewed {} tasks", n);
+            }
+            Command::Snooze { task, until, clear } => {
+                self.snooze_command(manager, task, until, *clear)?;
+                log::info!("task snoozed successfully");
+            }
+            Command::Snoozed => manager.list_snoozed()?,
+            Command::Recurring { action } => {
+                self.recurring_command(manager, action)?;
+                log::info!("recurring tasks updated successfully");
+            }
+            Command::Start { task } => {
+                manager.start_work(manager.resolve_id(task)?)?;
+                log::info!("started working on task");
+            }
+            Command::Stop => match manager.stop_work()? {
+                Some((task, spent)) => println!(
+                    "Stopped working on #{} {} after {}",
+                    task.id.unwrap(),
+                    task.short,
+                    time::format_duration(spent)
+                ),
+                None => println!("Not working on anything."),
             },
-
-            _ => todo!(),
+            Command::Timesheet { today, since, .. } => {
+                print!("{}", self.timesheet_command(manager, *today, since)?)
+            }
+            Command::Edit => {
+                self.edit_command(manager)?;
+                log::info!("task updated successfully");
+            }
+            Command::Estimates => print!("{}", manager.estimate_report()?),
+            Command::Stats {
+                weeks,
+                json,
+                charts,
+            } => {
+                let stats = manager.stats(*weeks)?;
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&stats)?);
+                } else if *charts {
+                    let width = crossterm::terminal::size().map_or(80, |(w, _)| w as usize);
+                    print!("{}", chart::render(&stats, width))
+                } else {
+                    print!("{}", stats)
+                }
+            }
+            Command::Search { terms } => {
+                let hits = manager.search(&terms.join(" "))?;
+                if hits.is_empty() {
+                    println!("No tasks found.");
+                }
+                let terminal = std::io::stdout().is_terminal();
+                for mut hit in hits {
+                    if !terminal {
+                        hit.strip_marks();
+                    }
+                    print!("{}", hit);
+                }
+            }
+            Command::Depend { action } => {
+                self.depend_command(manager, action)?;
+                log::info!("dependencies updated successfully");
+            }
+            Command::Add { parent, every } => {
+                let task = self.run_add_command(manager, parent)?;
+                manager.add(Task {
+                    recurrence: *every,
+                    ..task
+                })?;
+                log::info!("added task successfully");
+            }
+            Command::Show => manager.show(self.ask_task_id(manager)?)?,
+            Command::Remove => {
+                let (ids, subtasks) = self.run_remove_command(manager)?;
+                manager.remove_all(&ids, subtasks)?;
+                log::info!("removed task successfully");
+            }
+            Command::Done { task } => {
+                self.done_command(manager, task)?;
+                log::info!("task completed");
+            }
+            Command::SetStatus {
+                filter: Some(filter),
+                status: Some(status),
+            } => {
+                let n = self.bulk_command(
+                    manager,
+                    filter,
+                    &format!("Set the status to {}", status.to_string()),
+                    |t| t.status == *status,
+                    |manager, tasks| manager.set_status_all(tasks, status.clone()),
+                )?;
+                println!("{} tasks changed", n);
+            }
+            Command::SetStatus { .. } => {
+                self.set_status_command(manager)?;
+                log::info!("task updated successfully");
+            }
+            Command::Reprioritize { filter, prio } => {
+                let n = self.bulk_command(
+                    manager,
+                    filter,
+                    &format!("Move to {}", prio.to_string()),
+                    |t| t.prio == *prio,
+                    |manager, tasks| manager.set_priority_all(tasks, prio.clone()),
+                )?;
+                println!("{} tasks changed", n);
+            }
+            Command::Sync { remote, prefer } => {
+                let report = if Path::new(remote).is_file() {
+                    self.sync_file_command(manager, remote, *prefer)?
+                } else {
+                    manager.sync(remote, prefer.unwrap_or_default())?
+                };
+                print!("{}", report);
+            }
+            Command::Migrate { from, to, force } => {
+                let report = migrate::run(from, to, *force)?;
+                println!(
+                    "migrated {} tasks, checksum {:x}",
+                    report.tasks, report.checksum
+                );
+            }
         }
+        Ok(())
     }
 
     fn run_list_command(&self) -> Result<(FilterOptions, String)> {
@@ -120,7 +292,14 @@ impl App {
         }
     }
 
-    fn run_add_command(&self) -> Result<Task> {
+    fn run_add_command(&self, manager: &TaskManager, parent: &Option<String>) -> Result<Task> {
+        let parent = match parent {
+            Some(reference) => Some(manager.get_task(manager.resolve_id(reference)?)?),
+            None => None,
+        };
+        if let Some(p) = &parent {
+            println!("Adding a subtask to #{} {}", p.id.unwrap(), p.short);
+        }
         let name = Text::new("Task name?").prompt()?;
         let desc = Editor::new("What needs to be done?").prompt()?;
         let mut options: Vec<String> = vec![];
@@ -128,71 +307,835 @@ impl App {
             options.push(p.to_string());
         }
         let prio = Select::new("What priority to work on it?", options).prompt()?;
-        Ok(Task::new(
-            name,
-            desc,
-            TaskPriority::from_str(prio.as_str())?,
-        ))
+        let mut task = Task::new(name, desc, TaskPriority::from_str(prio.as_str())?);
+        task.parent = parent.map(|p| p.uuid);
+        task.estimate = self.ask_estimate(None)?;
+        Ok(task)
+    }
+
+    fn edit_command(&self, manager: &mut TaskManager) -> Result<usize> {
+        let id = self.ask_task_id(manager)?;
+        let task = manager.get_task(id)?;
+        let name = Text::new("Task name?")
+            .with_initial_value(&task.short)
+            .prompt()?;
+        let desc = Editor::new("What needs to be done?")
+            .with_predefined_text(&task.desc)
+            .prompt()?;
+        let options: Vec<String> = TaskPriority::iter().map(|p| p.to_string()).collect();
+        let current = TaskPriority::iter()
+            .position(|p| p == task.prio)
+            .unwrap_or(0);
+        let prio = TaskPriority::from_str(
+            &Select::new("What priority to work on it?", options)
+                .with_starting_cursor(current)
+                .prompt()?,
+        )?;
+        let estimate = self.ask_estimate(task.estimate)?;
+        let rank = match prio == task.prio {
+            true => task.rank,
+            false => None,
+        };
+        manager.set_status(
+            id,
+            Task {
+                short: name,
+                desc,
+                prio,
+                rank,
+                estimate,
+                ..task
+            },
+        )
     }
 
-    fn run_remove_command(&self) -> Result<u64> {
-        let uid = self.ask_task_id()?;
+    fn ask_estimate(&self, current: Option<Estimate>) -> Result<Option<Estimate>> {
+        let initial = current.map(|e| e.to_string()).unwrap_or_default();
+        loop {
+            let answer = Text::new("Estimate?")
+                .with_initial_value(&initial)
+                .with_help_message("e.g. 90m, 2h, 1d or 3pt, leave empty for none")
+                .prompt()?;
+            if answer.trim().is_empty() {
+                return Ok(None);
+            }
+            match answer.parse::<Estimate>() {
+                Ok(estimate) => return Ok(Some(estimate)),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+
+    fn run_remove_command(&self, manager: &TaskManager) -> Result<(Vec<u64>, Subtasks)> {
+        let ids = self.ask_task_ids(manager)?;
         println!("WARNING: The remove command ERASES the task from the database.");
         println!("This can not be undone. You could also just change status or mark the task done");
-        let confirm = Confirm::new("Are you really sure you want to delete this task?").prompt()?;
-        if confirm {
-            return Ok(uid);
+        let ask = match ids.len() {
+            1 => "Are you really sure you want to delete this task?".to_string(),
+            n => format!("Are you really sure you want to delete these {} tasks?", n),
+        };
+        let confirm = Confirm::new(&ask).prompt()?;
+        if !confirm {
+            return Err(Error::Cancelled);
+        }
+        let mut subtasks = 0;
+        for id in &ids {
+            subtasks += manager.subtasks(*id)?.len();
+        }
+        if subtasks == 0 {
+            return Ok((ids, Subtasks::Remove));
+        }
+        let options = vec![
+            "Remove the subtasks as well",
+            "Keep the subtasks, move them up one level",
+        ];
+        let ask = match ids.len() {
+            1 => format!("The task has {} subtasks, what about them?", subtasks),
+            _ => format!("The tasks have {} subtasks, what about them?", subtasks),
+        };
+        match Select::new(&ask, options).prompt()? {
+            "Remove the subtasks as well" => Ok((ids, Subtasks::Remove)),
+            _ => Ok((ids, Subtasks::Reparent)),
+        }
+    }
+
+    fn depend_command(&self, manager: &mut TaskManager, action: &DependCommand) -> Result<usize> {
+        match action {
+            DependCommand::Add { task, blocker } => {
+                manager.add_dependency(manager.resolve_id(task)?, manager.resolve_id(blocker)?)
+            }
+            DependCommand::Remove { task, blocker } => {
+                manager.remove_dependency(manager.resolve_id(task)?, manager.resolve_id(blocker)?)
+            }
+        }
+    }
+
+    fn move_command(
+        &self,
+        manager: &mut TaskManager,
+        task: &str,
+        before: &Option<String>,
+        after: &Option<String>,
+        top: bool,
+        bottom: bool,
+    ) -> Result<usize> {
+        let id = manager.resolve_id(task)?;
+        let placement = match (before, after) {
+            (Some(other), _) => Placement::Before(manager.resolve_id(other)?),
+            (_, Some(other)) => Placement::After(manager.resolve_id(other)?),
+            _ if top => Placement::Top,
+            _ if bottom => Placement::Bottom,
+            _ => {
+                return Err(Error::InvalidInput(
+                    "tell where to move the task to".to_string(),
+                ))
+            }
+        };
+        manager.move_task(id, placement)
+    }
+
+    fn reorder_command(&self, manager: &mut TaskManager) -> Result<usize> {
+        let prios: Vec<String> = TaskPriority::iter().map(|p| p.to_string()).collect();
+        let prio = TaskPriority::from_str(&Select::new("Priority?", prios).prompt()?)?;
+        let mut ids: Vec<u64> = manager
+            .bucket(&prio)?
+            .iter()
+            .filter(|t| t.status.is_open())
+            .map(|t| t.id.unwrap())
+            .collect();
+        if ids.is_empty() {
+            println!("No open tasks with priority {}.", prio.to_string());
+            return Ok(0);
+        }
+        let label = |manager: &TaskManager, id: u64| -> Result<String> {
+            Ok(format!("#{} {}", id, manager.get_task(id)?.short))
+        };
+        const DONE: &str = "Done";
+        loop {
+            let mut options = vec![DONE.to_string()];
+            for id in &ids {
+                options.push(label(manager, *id)?);
+            }
+            let picked = Select::new("Move which task?", options.clone()).prompt()?;
+            let from = match options.iter().position(|o| *o == picked) {
+                Some(0) | None => break,
+                Some(pos) => pos - 1,
+            };
+            let id = ids.remove(from);
+            let mut positions = vec!["Top".to_string()];
+            for other in &ids {
+                positions.push(format!("After {}", label(manager, *other)?));
+            }
+            let to = Select::new("Move it where?", positions.clone())
+                .with_starting_cursor(from)
+                .prompt()?;
+            let pos = positions.iter().position(|p| *p == to).unwrap_or(0);
+            ids.insert(pos, id);
+        }
+        manager.set_order(&prio, &ids)
+    }
+
+    fn review_command(
+        &self,
+        manager: &mut TaskManager,
+        thresholds: &[Threshold],
+        since_created: bool,
+    ) -> Result<usize> {
+        let stale = manager.review(thresholds, since_created)?;
+        if stale.is_empty() {
+            println!("Nothing to review.");
+            return Ok(0);
+        }
+        let options: Vec<String> = Action::iter().map(|a| a.to_string()).collect();
+        const STOP: &str = "Stop reviewing";
+        let mut reviewed = 0;
+        for s in &stale {
+            println!(
+                "#{} {} | {} | untouched for {} days",
+                s.task.id.unwrap(),
+                s.task.short,
+                s.task.prio.to_string(),
+                s.days
+            );
+            let mut choices = options.clone();
+            choices.push(STOP.to_string());
+            let suggested = Action::iter().position(|a| a == s.suggestion).unwrap_or(0);
+            let choice = Select::new("What to do with it?", choices)
+                .with_starting_cursor(suggested)
+                .prompt()?;
+            let action = match Action::iter().find(|a| a.to_string() == choice) {
+                Some(action) => action,
+                None => break,
+            };
+            manager.apply_review(s.task.id.unwrap(), action)?;
+            reviewed += 1;
+        }
+        Ok(reviewed)
+    }
+
+    fn weekly_review_command(&self, manager: &mut TaskManager) -> Result<Summary> {
+        let mut summary = Summary::default();
+        let actions = [
+            ('k', "keep"),
+            ('p', "reprioritize"),
+            ('s', "change status"),
+            ('a', "archive"),
+            ('n', "skip"),
+            ('q', "quit"),
+        ];
+        for prio in review::weekly_buckets() {
+            let ids: Vec<u64> = manager
+                .bucket(&prio)?
+                .iter()
+                .filter(|t| t.status.is_open())
+                .map(|t| t.id.unwrap())
+                .collect();
+            if ids.is_empty() {
+                continue;
+            }
+            println!("\n{} ({} open tasks)", prio.to_string(), ids.len());
+            for id in ids {
+                let task = manager.get_task(id)?;
+                // completing an earlier task may have closed its subtasks as well
+                if !task.status.is_open() {
+                    continue;
+                }
+                manager.show(id)?;
+                match self.ask_shortcut("Review:", &actions)? {
+                    'k' => {
+                        manager.apply_review(id, Action::Keep)?;
+                        summary.kept += 1;
+                    }
+                    'p' => {
+                        let prios: Vec<String> = TaskPriority::iter()
+                            .filter(|p| *p != task.prio)
+                            .map(|p| p.to_string())
+                            .collect();
+                        let new =
+                            TaskPriority::from_str(&Select::new("New priority?", prios).prompt()?)?;
+                        manager.set_priority(id, new.clone())?;
+                        summary.changed(
+                            &task,
+                            format!("{} -> {}", task.prio.to_string(), new.to_string()),
+                        );
+                    }
+                    's' => {
+                        let statuses: Vec<String> = TaskStatus::iter()
+                            .filter(|s| *s != task.status)
+                            .map(|s| s.to_string())
+                            .collect();
+                        let new =
+                            TaskStatus::from_str(&Select::new("New status?", statuses).prompt()?)?;
+                        if new == TaskStatus::Completed {
+                            let with_subtasks = self.ask_complete_subtasks(manager, id)?;
+                            manager.done(id, with_subtasks)?;
+                        } else {
+                            manager.set_status(
+                                id,
+                                Task {
+                                    status: new.clone(),
+                                    ..task.clone()
+                                },
+                            )?;
+                        }
+                        summary.changed(
+                            &task,
+                            format!("{} -> {}", task.status.to_string(), new.to_string()),
+                        );
+                    }
+                    'a' => {
+                        manager.apply_review(id, Action::Archive)?;
+                        summary.changed(&task, "archived".to_string());
+                    }
+                    'n' => summary.skipped += 1,
+                    _ => return Ok(summary),
+                }
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Asks for one of `keys` by its letter.
+    fn ask_shortcut(&self, prompt: &str, keys: &[(char, &str)]) -> Result<char> {
+        let help: Vec<String> = keys
+            .iter()
+            .map(|(key, name)| format!("{} {}", key, name))
+            .collect();
+        loop {
+            let answer = Text::new(prompt)
+                .with_help_message(&help.join(", "))
+                .prompt()?;
+            match answer.trim().to_lowercase().chars().next() {
+                Some(c) if keys.iter().any(|(key, _)| *key == c) => return Ok(c),
+                _ => println!("Please answer with one of: {}", help.join(", ")),
+            }
         }
-        Err(Error::UserAbort)
     }
 
-    fn ask_task_id(&self) -> Result<u64> {
-        let strid = Text::new("Task id?").prompt()?;
-        let uid = strid.parse::<u64>()?;
-        Ok(uid)
+    fn snooze_command(
+        &self,
+        manager: &mut TaskManager,
+        task: &str,
+        until: &Option<String>,
+        clear: bool,
+    ) -> Result<usize> {
+        let id = manager.resolve_id(task)?;
+        if clear {
+            return manager.snooze(id, None);
+        }
+        let until = match until {
+            Some(until) => date::parse_date(until, Utc::now())?,
+            None => {
+                let day = DateSelect::new("Snooze until?")
+                    .with_min_date(Local::now().date_naive() + Duration::days(1))
+                    .with_starting_date(Local::now().date_naive() + Duration::days(7))
+                    .prompt()?;
+                date::start_of_day(day)?
+            }
+        };
+        manager.snooze(id, Some(until))
+    }
+
+    fn recurring_command(
+        &self,
+        manager: &mut TaskManager,
+        action: &Option<RecurringCommand>,
+    ) -> Result<usize> {
+        match action {
+            Some(RecurringCommand::Set { task, rule }) => {
+                manager.set_recurrence(manager.resolve_id(task)?, Some(*rule))
+            }
+            Some(RecurringCommand::Stop { task }) => manager.stop_series(manager.resolve_id(task)?),
+            None => {
+                let tasks = manager.recurring()?;
+                if tasks.is_empty() {
+                    println!("No recurring tasks.");
+                }
+                for t in &tasks {
+                    let due = match t.due {
+                        Some(due) => format!(", next on {}", due.format("%Y-%m-%d")),
+                        None => String::new(),
+                    };
+                    println!(
+                        "#{} {} | {}{}",
+                        t.id.unwrap(),
+                        t.short,
+                        t.recurrence.unwrap(),
+                        due
+                    );
+                }
+                Ok(0)
+            }
+        }
+    }
+
+    fn timesheet_command(
+        &self,
+        manager: &TaskManager,
+        today: bool,
+        since: &Option<String>,
+    ) -> Result<Timesheet> {
+        let now = Utc::now();
+        let day = Local::now().date_naive();
+        let from = match since {
+            Some(since) => date::parse_date(since, now)?,
+            None if today => date::start_of_day(day)?,
+            None => date::start_of_day(
+                day - Duration::days(day.weekday().num_days_from_monday() as i64),
+            )?,
+        };
+        manager.timesheet(from, now)
+    }
+
+    fn done_command(&self, manager: &mut TaskManager, task: &Option<String>) -> Result<usize> {
+        let id = match task {
+            Some(reference) => manager.resolve_id(reference)?,
+            None => self.ask_task_id(manager)?,
+        };
+        let with_subtasks = self.ask_complete_subtasks(manager, id)?;
+        manager.done(id, with_subtasks)
+    }
+
+    /// Asks what to do about open subtasks when completing a task.
+    fn ask_complete_subtasks(&self, manager: &TaskManager, id: u64) -> Result<bool> {
+        let open = manager.open_subtasks(id)?;
+        if open.is_empty() {
+            return Ok(false);
+        }
+        println!("This task still has {} open subtasks:", open.len());
+        for t in &open {
+            println!("  #{} {}", t.id.unwrap(), t.short);
+        }
+        let options = vec!["Complete them as well", "Only complete this task", "Cancel"];
+        match Select::new("What about the subtasks?", options).prompt()? {
+            "Complete them as well" => Ok(true),
+            "Only complete this task" => Ok(false),
+            _ => Err(Error::Cancelled),
+        }
+    }
+
+    /// Picks an open task from a fuzzy searchable list, or asks for the id of any other task.
+    fn ask_task_id(&self, manager: &TaskManager) -> Result<u64> {
+        let picks = self.task_picks(manager)?;
+        if picks.len() == 1 {
+            return self.ask_typed_ids(manager).map(|ids| ids[0]);
+        }
+        let scorer = pick_scorer(manager);
+        match Select::new("Task?", picks)
+            .with_scorer(&scorer)
+            .with_help_message("type to filter, or pick the last entry to enter an id")
+            .prompt()?
+        {
+            Pick::Task(task) => Ok(task.id.unwrap()),
+            Pick::Typed => self.ask_typed_ids(manager).map(|ids| ids[0]),
+        }
+    }
+
+    /// Like `ask_task_id`, but any number of tasks can be picked.
+    fn ask_task_ids(&self, manager: &TaskManager) -> Result<Vec<u64>> {
+        let picks = self.task_picks(manager)?;
+        if picks.len() == 1 {
+            return self.ask_typed_ids(manager);
+        }
+        let scorer = pick_scorer(manager);
+        let picked = MultiSelect::new("Tasks?", picks)
+            .with_scorer(&scorer)
+            .with_help_message("type to filter, space to pick, pick the last entry to enter ids")
+            .prompt()?;
+        let mut ids = vec![];
+        for pick in picked {
+            match pick {
+                Pick::Task(task) => ids.push(task.id.unwrap()),
+                Pick::Typed => ids.extend(self.ask_typed_ids(manager)?),
+            }
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        match ids.is_empty() {
+            true => Err(Error::Cancelled),
+            false => Ok(ids),
+        }
+    }
+
+    /// The open tasks in list order, followed by the entry to type an id.
+    fn task_picks(&self, manager: &TaskManager) -> Result<Vec<Pick>> {
+        let mut picks: Vec<Pick> = manager
+            .open_tasks()?
+            .into_iter()
+            .map(|t| Pick::Task(Box::new(t)))
+            .collect();
+        picks.push(Pick::Typed);
+        Ok(picks)
+    }
+
+    /// Asks for one or more task ids or uuid prefixes, separated by commas or spaces.
+    fn ask_typed_ids(&self, manager: &TaskManager) -> Result<Vec<u64>> {
+        let answer = Text::new("Task id or uuid prefix?").prompt()?;
+        let ids = answer
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|r| !r.is_empty())
+            .map(|r| manager.resolve_id(r))
+            .collect::<Result<Vec<u64>>>()?;
+        match ids.is_empty() {
+            true => Err(Error::Cancelled),
+            false => Ok(ids),
+        }
+    }
+
+    fn sync_file_command(
+        &self,
+        manager: &mut TaskManager,
+        path: &String,
+        prefer: Option<MergePolicy>,
+    ) -> Result<SyncReport> {
+        let kind = match Path::new(path).extension() {
+            Some(ext) if ext == "json" => Datasources::Json,
+            _ => Datasources::SqlLite,
+        };
+        let mut other: Box<dyn Datasource> = source::open(kind, path)?;
+        manager.sync_with(other.as_mut(), &mut |ours, theirs| match prefer {
+            Some(policy) => Ok(policy),
+            None => self.ask_conflict(ours, theirs),
+        })
+    }
+
+    fn ask_conflict(&self, ours: &Task, theirs: &Task) -> Result<MergePolicy> {
+        println!(
+            "Task \"{}\" was changed in both databases: {}",
+            ours.short,
+            merge::differing_fields(ours, theirs).join(", ")
+        );
+        let describe = |side: &str, t: &Task| {
+            format!(
+                "{}: {} | {} | {} | modified {}",
+                side,
+                t.short,
+                t.prio.to_string(),
+                t.status.to_string(),
+                t.modified.format("%Y-%m-%d %H:%M")
+            )
+        };
+        let options = vec![describe("ours", ours), describe("theirs", theirs)];
+        let newest = match MergePolicy::Newest.side(ours, theirs) {
+            MergePolicy::Ours => 0,
+            _ => 1,
+        };
+        let choice = Select::new("Keep which version?", options.clone())
+            .with_starting_cursor(newest)
+            .prompt()?;
+        if choice == options[0] {
+            return Ok(MergePolicy::Ours);
+        }
+        Ok(MergePolicy::Theirs)
+    }
+
+    /// Shows the tasks `filter` selects, leaving out those `unchanged` says the change
+    /// doesn't affect, and applies the change to them once confirmed.
+    fn bulk_command(
+        &self,
+        manager: &mut TaskManager,
+        filter: &Filter,
+        change: &str,
+        unchanged: impl Fn(&Task) -> bool,
+        apply: impl FnOnce(&mut TaskManager, &[Task]) -> Result<usize>,
+    ) -> Result<usize> {
+        let mut tasks = manager.select(filter)?;
+        tasks.retain(|t| !unchanged(t));
+        if tasks.is_empty() {
+            println!("No tasks to change.");
+            return Ok(0);
+        }
+        for t in &tasks {
+            println!(
+                "  #{} {} | {} | {}",
+                t.id.unwrap(),
+                t.short,
+                t.prio.to_string(),
+                t.status.to_string()
+            );
+        }
+        let ask = format!("{} for these {} tasks?", change, tasks.len());
+        if !Confirm::new(&ask).prompt()? {
+            return Err(Error::Cancelled);
+        }
+        apply(manager, &tasks)
     }
 
     fn set_status_command(&self, manager: &mut TaskManager) -> Result<()> {
-        let uid = self.ask_task_id()?;
-        let mut task = manager.get_task(uid)?;
-        let current_status = task.status.to_string();
-        println!("Current status is: {}", current_status);
+        let ids = self.ask_task_ids(manager)?;
+        let mut current_status = None;
+        if let [uid] = ids.as_slice() {
+            let status = manager.get_task(*uid)?.status.to_string();
+            println!("Current status is: {}", status);
+            current_status = Some(status);
+        }
 
         let mut options: Vec<String> = vec![];
         for st in TaskStatus::iter() {
-            if st.to_string() != current_status {
+            if Some(st.to_string()) != current_status {
                 options.push(st.to_string())
             }
         }
         let new_status = inquire::Select::new("New Status?", options).prompt()?;
-        let ask = format!("Set new status to {}?", new_status);
+        let ask = match ids.len() {
+            1 => format!("Set new status to {}?", new_status),
+            n => format!("Set new status of {} tasks to {}?", n, new_status),
+        };
         let confirm = Confirm::new(&ask).prompt()?;
         if confirm {
-            let st = match TaskStatus::from_str(&new_status) {
-                Ok(st) => st,
-                Err(_) => return Err(crate::error::Error::ParsingError),
-            };
+            let st = TaskStatus::from_str(&new_status)?;
             log::debug!("new_status: {}", st.to_string());
-            task.status = st;
-            manager.set_status(uid, task)?;
+            for uid in ids {
+                if st == TaskStatus::Completed {
+                    let with_subtasks = self.ask_complete_subtasks(manager, uid)?;
+                    manager.done(uid, with_subtasks)?;
+                    continue;
+                }
+                let mut task = manager.get_task(uid)?;
+                task.status = st.clone();
+                manager.set_status(uid, task)?;
+            }
         }
         Ok(())
     }
 }
 
+/// An entry of the task picker.
+enum Pick {
+    Task(Box<Task>),
+    /// enter the id of a task which isn't listed
+    Typed,
+}
+
+impl Display for Pick {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pick::Task(t) => write!(
+                f,
+                "#{:<4} {:<22} {}",
+                t.id.unwrap_or_default(),
+                t.prio.to_string(),
+                t.short
+            ),
+            Pick::Typed => write!(f, "Other task, enter its id or uuid prefix…"),
+        }
+    }
+}
+
+/// Fuzzy matches the picker entries, puts the task a typed id, working id or uuid prefix
+/// refers to first and always offers to enter an id.
+fn pick_scorer(manager: &TaskManager) -> impl Fn(&str, &Pick, &str, usize) -> Option<i64> + '_ {
+    // the input is resolved once, not once per entry
+    let typed: RefCell<Option<(String, Option<u64>)>> = RefCell::new(None);
+    move |input, pick, value, idx| {
+        let mut typed = typed.borrow_mut();
+        if typed.as_ref().is_none_or(|(last, _)| last != input) {
+            *typed = Some((input.to_string(), manager.resolve_id(input).ok()));
+        }
+        let referenced = typed.as_ref().and_then(|(_, id)| *id);
+        match pick {
+            Pick::Typed => Some(i64::MIN),
+            Pick::Task(t) if t.id.is_some() && t.id == referenced => Some(i64::MAX),
+            Pick::Task(_) => Select::<Pick>::DEFAULT_SCORER(input, pick, value, idx),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 pub enum Command {
-    Add,
+    Add {
+        /// Add the new task as a subtask of this task
+        #[arg(long)]
+        parent: Option<String>,
+        /// Make the task recur, e.g. daily, weekly on fri, every 2w or monthly on 1
+        #[arg(long)]
+        every: Option<Recurrence>,
+    },
     Remove,
+    /// Change the name, description, priority or estimate of a task
     Edit,
-    List,
-    SetStatus,
+    List {
+        /// Only show open tasks which are not blocked, in the order they can be worked on
+        #[arg(short, long)]
+        actionable: bool,
+        /// Sort by these fields, e.g. prio,status,-created,name. A leading - sorts
+        /// descending. Fields: id, name, status, prio, created, started, modified, rank
+        #[arg(short, long, value_delimiter = ',')]
+        sort: Vec<SortKey>,
+    },
+    /// Show what to work on next, and why
+    Next {
+        /// How many tasks to show
+        #[arg(short, default_value_t = 1)]
+        n: usize,
+    },
+    /// Move a task within its priority, e.g. move 3 --before 1
+    Move {
+        /// Task id, working id or uuid prefix
+        task: String,
+        /// Put it right before this task, taking over its priority
+        #[arg(long, group = "place")]
+        before: Option<String>,
+        /// Put it right after this task, taking over its priority
+        #[arg(long, group = "place")]
+        after: Option<String>,
+        /// Make it the first task of its priority
+        #[arg(long, group = "place")]
+        top: bool,
+        /// Make it the last task of its priority
+        #[arg(long, group = "place")]
+        bottom: bool,
+    },
+    /// Interactively order the open tasks of a priority
+    Reorder,
+    /// Go through tasks which sat in their priority for too long, and promote, demote,
+    /// archive or drop them
+    Review {
+        /// Walk through all open tasks bucket by bucket instead, starting with Watch and Some Day
+        #[arg(long, conflicts_with_all = ["threshold", "since_created", "save"])]
+        weekly: bool,
+        /// Days a task may stay untouched in a priority, e.g. --threshold some-day=90.
+        /// Defaults: urgent and important 3, urgent not important 7, important not urgent
+        /// and to do 30, watch 14, nice to do 60, some day 90
+        #[arg(short, long)]
+        threshold: Vec<Threshold>,
+        /// Keep the given thresholds for later reviews
+        #[arg(long, requires = "threshold")]
+        save: bool,
+        /// Count the age from when a task was created instead of when it last changed
+        #[arg(long)]
+        since_created: bool,
+    },
+    /// Hide a task from list and next until a date
+    Snooze {
+        /// Task id, working id or uuid prefix
+        task: String,
+        /// A date like 2026-11-02 or a time from now like +3d, +2w or +12h. Asks with a
+        /// calendar if not given
+        #[arg(long, conflicts_with = "clear")]
+        until: Option<String>,
+        /// Show the task again right away
+        #[arg(long)]
+        clear: bool,
+    },
+    /// List the snoozed tasks
+    Snoozed,
+    /// List recurring tasks, or start and stop a series
+    Recurring {
+        #[command(subcommand)]
+        action: Option<RecurringCommand>,
+    },
+    /// Start working on a task, stopping the task worked on before
+    Start {
+        /// Task id, working id or uuid prefix
+        task: String,
+    },
+    /// Stop working on the current task
+    Stop,
+    /// Show the time worked, by task, group and priority. Defaults to this week
+    Timesheet {
+        /// Since monday
+        #[arg(long, conflicts_with_all = ["today", "since"])]
+        week: bool,
+        /// Since midnight
+        #[arg(long, conflicts_with = "since")]
+        today: bool,
+        /// Since a date like 2026-11-02, or a time span ago like -2w
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+    },
+    /// Compare estimates with the time tracked on completed tasks
+    Estimates,
+    /// Show open work, tasks created and completed per week and lead times
+    Stats {
+        /// Number of weeks to count created and completed tasks for, up to ten years
+        #[arg(long, default_value_t = 8, value_parser = RangedU64ValueParser::<usize>::new().range(1..=520))]
+        weeks: usize,
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+        /// Draw the trends as charts
+        #[arg(long, conflicts_with = "json")]
+        charts: bool,
+    },
+    /// Search names and descriptions, e.g. search "release notes" deploy*
+    Search {
+        #[arg(required = true)]
+        terms: Vec<String>,
+    },
+    /// Manage which tasks are blocked by other tasks
+    Depend {
+        #[command(subcommand)]
+        action: DependCommand,
+    },
+    /// Change the status of a task, or of all tasks matching --filter,
+    /// e.g. set-status --filter 'group:release-1' Archived
+    SetStatus {
+        /// Tasks to change, e.g. 'prio:Watch status:Created', 'group:<top level task>' or 'name:<part>'
+        #[arg(long, requires = "status")]
+        filter: Option<Filter>,
+        /// The new status, needed with --filter
+        #[arg(requires = "filter", value_parser = filter::parse_status)]
+        status: Option<TaskStatus>,
+    },
+    /// Move all tasks matching --filter to another priority,
+    /// e.g. reprioritize --filter 'prio:Watch status:Created' SomeDay
+    Reprioritize {
+        /// Tasks to change, e.g. 'prio:Watch status:Created', 'group:<top level task>' or 'name:<part>'
+        #[arg(long)]
+        filter: Filter,
+        #[arg(value_parser = filter::parse_priority)]
+        prio: TaskPriority,
+    },
     Show,
+    /// Mark a task as completed
+    Done {
+        /// Task id, working id or uuid prefix
+        task: Option<String>,
+    },
+    /// Merge the tasks of another database file, or pull and merge a git remote (requires --git)
+    Sync {
+        /// A database file, a remote name or path to another repository
+        #[arg(default_value = "origin")]
+        remote: String,
+        /// Which side wins if a task was changed on both sides. Database files ask
+        /// interactively if not given, git remotes default to theirs
+        #[arg(long, value_enum)]
+        prefer: Option<MergePolicy>,
+    },
+    /// Copy all tasks from one datasource to another, e.g. --from sqllite:~/mytasks.sql --to json:~/tasks.json
+    Migrate {
+        #[arg(long)]
+        from: DatasourceSpec,
+        #[arg(long)]
+        to: DatasourceSpec,
+        /// Overwrite the target even if it already contains tasks
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DependCommand {
+    /// Task can't be worked on before blocker is done
+    Add { task: String, blocker: String },
+    /// Task no longer waits for blocker
+    Remove { task: String, blocker: String },
+}
+
+#[derive(Subcommand)]
+pub enum RecurringCommand {
+    /// Make a task recur, completing it creates the next one
+    Set { task: String, rule: Recurrence },
+    /// Stop the series a task belongs to
+    Stop { task: String },
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum Datasources {
+    #[value(alias = "sqllite")]
     SqlLite,
+    Json,
 }
 
 pub struct NoFilter {}
@@ -238,9 +1181,67 @@ mod tests {
 
     #[test]
     fn default_settings() {
-        let settings = default_sqllite_settings();
+        let settings = default_sqllite_settings().unwrap();
         assert_eq!(settings.datasource, Datasources::SqlLite);
         let db = home::home_dir().unwrap().join(TASKS_DB_FILE_NAME);
         assert_eq!(settings.path, db.to_string_lossy());
     }
+
+    #[test]
+    fn stats_weeks_in_range() {
+        let weeks = |n: &str| match App::try_parse_from(["myprio", "stats", "--weeks", n]) {
+            Ok(App {
+                command: Command::Stats { weeks, .. },
+                ..
+            }) => Some(weeks),
+            _ => None,
+        };
+        assert_eq!(weeks("520"), Some(520));
+        assert_eq!(weeks("0"), None);
+        assert_eq!(weeks("20000000"), None);
+    }
+
+    #[test]
+    fn pick_by_reference() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut manager = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        for (name, prio) in [
+            ("later", TaskPriority::SomeDay),
+            ("now", TaskPriority::UrgentAndImportant),
+            ("soon", TaskPriority::ToDo),
+        ] {
+            manager.add(Task::new(name.to_string(), "".to_string(), prio))?;
+        }
+        let first = |manager: &TaskManager, input: &str| -> Result<String> {
+            let scorer = pick_scorer(manager);
+            let mut picks: Vec<Pick> = manager
+                .open_tasks()?
+                .into_iter()
+                .map(|t| Pick::Task(Box::new(t)))
+                .collect();
+            picks.push(Pick::Typed);
+            let best = picks
+                .iter()
+                .enumerate()
+                .max_by_key(|(idx, p)| scorer(input, p, &p.to_string(), *idx))
+                .unwrap();
+            Ok(match best.1 {
+                Pick::Task(t) => t.short.clone(),
+                Pick::Typed => "typed".to_string(),
+            })
+        };
+
+        assert_eq!(first(&manager, "1")?, "later");
+        assert_eq!(first(&manager, "#3")?, "soon");
+        let uuid = manager.get_task(2)?.uuid.to_string();
+        assert_eq!(first(&manager, &uuid[..6])?, "now");
+        assert_eq!(first(&manager, "soo")?, "soon");
+
+        // listed as 1 now, 2 soon, 3 later
+        manager.use_working_ids(true);
+        manager.list(&None, &[])?;
+        assert_eq!(first(&manager, "1")?, "now");
+        assert_eq!(first(&manager, "#1")?, "later");
+        Ok(())
+    }
 }
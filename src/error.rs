@@ -1,19 +1,108 @@
+use std::fmt::Display;
 use std::num::ParseIntError;
+use std::process::ExitCode;
 
-use inquire;
+use inquire::{self, InquireError};
 use rusqlite;
 
-use crate::task::NoSuchStatusError;
-
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Any error of the datasource, kept as the source of `Error::Storage`.
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 #[derive(Debug)]
 pub enum Error {
-    TaskNotFound,
-    IOError(String),
-    InquireError,
-    ParsingError,
-    UserAbort,
+    /// no task with this id, working id or uuid prefix
+    NotFound {
+        id: String,
+    },
+    /// a uuid prefix shared by several tasks
+    AmbiguousTaskId(String),
+    DependencyCycle,
+    InvalidStatus(String),
+    InvalidPriority(String),
+    /// input which could not be understood, like a date or a command line option
+    InvalidInput(String),
+    /// the datasource could not be read or written
+    Storage {
+        source: BoxedError,
+    },
+    /// another process kept the database locked for longer than we waited
+    DatabaseBusy {
+        source: rusqlite::Error,
+    },
+    /// the change contradicts data which is already there
+    Conflict(String),
+    /// a prompt could not be shown
+    Prompt {
+        source: InquireError,
+    },
+    /// the user decided not to go on
+    Cancelled,
+}
+
+impl Error {
+    pub fn not_found(id: impl ToString) -> Self {
+        Self::NotFound { id: id.to_string() }
+    }
+
+    pub fn storage(source: impl Into<BoxedError>) -> Self {
+        Self::Storage {
+            source: source.into(),
+        }
+    }
+
+    /// The exit code of the process failing with this error, see the README.
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Self::Prompt { .. } => 1,
+            Self::AmbiguousTaskId(_)
+            | Self::DependencyCycle
+            | Self::InvalidStatus(_)
+            | Self::InvalidPriority(_)
+            | Self::InvalidInput(_) => 2,
+            Self::NotFound { .. } => 3,
+            Self::Conflict(_) => 4,
+            Self::Storage { .. } => 5,
+            Self::DatabaseBusy { .. } => 6,
+            Self::Cancelled => 130,
+        })
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound { id } => write!(f, "no task {}", id),
+            Self::AmbiguousTaskId(prefix) => {
+                write!(
+                    f,
+                    "several tasks start with {}, give more of the uuid",
+                    prefix
+                )
+            }
+            Self::DependencyCycle => write!(f, "the tasks would block each other"),
+            Self::InvalidStatus(s) => write!(f, "unknown status {}", s),
+            Self::InvalidPriority(s) => write!(f, "unknown priority {}", s),
+            Self::InvalidInput(s) => write!(f, "{}", s),
+            Self::Storage { .. } => write!(f, "failed to access the tasks"),
+            Self::DatabaseBusy { .. } => write!(f, "the database is in use, try again"),
+            Self::Conflict(s) => write!(f, "{}", s),
+            Self::Prompt { .. } => write!(f, "failed to ask for input"),
+            Self::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Storage { source } => Some(source.as_ref()),
+            Self::DatabaseBusy { source } => Some(source),
+            Self::Prompt { source } => Some(source),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -21,42 +110,66 @@ pub struct EnumParseError;
 
 impl From<rusqlite::Error> for Error {
     fn from(err: rusqlite::Error) -> Self {
-        Self::IOError(err.to_string())
-    }
-}
-
-impl From<NoSuchStatusError> for Error {
-    fn from(err: NoSuchStatusError) -> Self {
-        Self::IOError(err.to_string())
+        match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) => {
+                Self::DatabaseBusy { source: err }
+            }
+            _ => Self::storage(err),
+        }
     }
 }
 
 impl From<ParseIntError> for Error {
     fn from(err: ParseIntError) -> Self {
-        log::error!("Failed to parse data, check format: {}", err);
-        Self::ParsingError
+        Self::InvalidInput(format!("not a number: {}", err))
     }
 }
 
-impl From<inquire::InquireError> for Error {
-    fn from(err: inquire::InquireError) -> Self {
-        log::error!(
-            "Prompting for information generated an unlikely error: {}.",
-            err
-        );
-        Self::InquireError
+impl From<InquireError> for Error {
+    fn from(err: InquireError) -> Self {
+        match err {
+            InquireError::OperationCanceled | InquireError::OperationInterrupted => Self::Cancelled,
+            err => Self::Prompt { source: err },
+        }
     }
 }
 
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
-        Self::IOError(err.to_string())
+        Self::storage(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::storage(err)
     }
 }
 
 impl From<EnumParseError> for Error {
     fn from(_err: EnumParseError) -> Self {
-        log::error!("Selected option does not exist");
-        Self::ParsingError
+        Self::InvalidInput("selected option does not exist".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn chains_sources() {
+        let err = Error::from(rusqlite::Error::InvalidQuery);
+        assert_eq!(err.to_string(), "failed to access the tasks");
+        assert_eq!(
+            err.source().unwrap().to_string(),
+            rusqlite::Error::InvalidQuery.to_string()
+        );
+        assert_eq!(err.exit_code(), ExitCode::from(5));
+
+        let err = Error::from(InquireError::OperationCanceled);
+        assert!(matches!(err, Error::Cancelled));
+        assert!(err.source().is_none());
+        assert_eq!(Error::not_found(12).to_string(), "no task 12");
     }
 }
@@ -1,19 +1,60 @@
+use std::error::Error as _;
+use std::process::ExitCode;
+
 use clap::Parser;
 use myprio::app::{App, Datasources};
+use myprio::error::Error;
 use myprio::task::manager::TaskManager;
 
-fn main() {
+fn main() -> ExitCode {
     env_logger::init();
     let app = App::parse();
-    //let path = &app.path;
-    let default_settings = myprio::app::default_sqllite_settings();
-    let path = &default_settings.path;
+    let path = match &app.path {
+        Some(path) => path.clone(),
+        None => match myprio::app::default_sqllite_settings() {
+            Ok(settings) => settings.path,
+            Err(e) => {
+                eprintln!("myprio: {}", e);
+                return e.exit_code();
+            }
+        },
+    };
     let ds = match app.datasource {
         None => Datasources::SqlLite,
         Some(ds) => ds,
     };
 
-    let mgr = &mut TaskManager::new(ds, path);
+    let mgr = TaskManager::builder(ds, &path)
+        .git(app.git)
+        .working_ids(app.working_ids)
+        .build();
+    let mgr = &mut match mgr {
+        Ok(mgr) => mgr,
+        Err(e) => {
+            eprintln!("myprio: can't open the tasks at {}: {}", path, e);
+            print_causes(&e);
+            if let Error::Storage { .. } = e {
+                eprintln!("Check that the directory exists and that you may write to it,");
+                eprintln!("or keep the tasks somewhere else with --path <file>.");
+            }
+            return e.exit_code();
+        }
+    };
+
+    match app.run_prompt(mgr) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("myprio: {}", e);
+            print_causes(&e);
+            e.exit_code()
+        }
+    }
+}
 
-    app.run_prompt(mgr);
+fn print_causes(e: &Error) {
+    let mut source = e.source();
+    while let Some(cause) = source {
+        eprintln!("  caused by: {}", cause);
+        source = cause.source();
+    }
 }
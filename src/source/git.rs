@@ -0,0 +1,530 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log;
+
+use crate::app::{Datasources, FilterOptions};
+use crate::error::{Error, Result};
+use crate::source::{self, Datasource};
+use crate::task::deps::Dependency;
+use crate::task::merge::{self, MergePolicy, SyncReport, Tombstone};
+use crate::task::search::Hit;
+use crate::task::sort::SortKey;
+use crate::task::time::Session;
+use crate::task::{Task, Timestamp};
+use uuid::Uuid;
+
+/// Wraps a file based datasource and commits the datasource file to a git repository
+/// after every change, so the task history is versioned and can be shared via `sync`.
+pub struct GitDataSource {
+    inner: Box<dyn Datasource>,
+    kind: Datasources,
+    dir: PathBuf,
+    file: String,
+    /// commit messages of the changes made in the open transactions
+    pending: Vec<String>,
+    /// how many messages were pending when each open transaction began, innermost last
+    savepoints: Vec<usize>,
+}
+
+impl GitDataSource {
+    pub fn new(inner: Box<dyn Datasource>, kind: Datasources, path: &String) -> Result<Self> {
+        let path = Path::new(path);
+        let dir = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file = match path.file_name() {
+            Some(f) => f.to_string_lossy().to_string(),
+            None => {
+                return Err(Error::storage(format!(
+                    "{} is not a file",
+                    path.to_string_lossy()
+                )))
+            }
+        };
+        let mut ds = Self {
+            inner,
+            kind,
+            dir,
+            file,
+            pending: vec![],
+            savepoints: vec![],
+        };
+        if !ds.dir.join(".git").exists() {
+            log::info!(
+                "initializing git repository at {}",
+                ds.dir.to_string_lossy()
+            );
+            ds.git(&["init", "-q"])?;
+        }
+        ds.commit("track task database")?;
+        Ok(ds)
+    }
+
+    fn git(&self, args: &[&str]) -> Result<Vec<u8>> {
+        log::debug!("git {}", args.join(" "));
+        let out = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()?;
+        if !out.status.success() {
+            return Err(Error::storage(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&out.stderr).trim()
+            )));
+        }
+        Ok(out.stdout)
+    }
+
+    /// Commits the datasource file, or only notes the change while a transaction is open.
+    fn commit(&mut self, message: &str) -> Result<()> {
+        if !self.savepoints.is_empty() {
+            self.pending.push(message.to_string());
+            return Ok(());
+        }
+        self.inner.flush()?;
+        if self
+            .git(&["status", "--porcelain", "--", &self.file])?
+            .is_empty()
+        {
+            log::debug!("nothing to commit for: {}", message);
+            return Ok(());
+        }
+        self.git(&["add", "--", &self.file])?;
+        self.git(&["commit", "-q", "-m", message, "--", &self.file])?;
+        Ok(())
+    }
+
+    /// Loads all tasks, dependencies and work sessions of the datasource file as it was at git revision `rev`.
+    /// A revision which doesn't contain the file yet has no tasks.
+    fn snapshot(&self, rev: &str) -> Result<(Vec<Task>, Vec<Dependency>, Vec<Session>)> {
+        let mut tasks = vec![];
+        let data = match self.git(&["show", &format!("{}:./{}", rev, self.file)]) {
+            Ok(data) => data,
+            Err(_) => return Ok((tasks, vec![], vec![])),
+        };
+        let tmp = tempfile::NamedTempFile::new()?;
+        fs::write(tmp.path(), data)?;
+        let ds = source::open(self.kind, &tmp.path().to_string_lossy().to_string())?;
+        ds.list(&mut tasks, &None)?;
+        Ok((tasks, ds.dependencies()?, ds.sessions()?))
+    }
+
+    /// "task 12" if the task is known, its uuid otherwise.
+    fn label(&self, uuid: &Uuid) -> String {
+        match self.inner.find_by_uuid(&uuid.to_string()) {
+            Ok(tasks) if !tasks.is_empty() => format!("task {}", tasks[0].id.unwrap()),
+            _ => format!("task {}", uuid),
+        }
+    }
+
+    fn apply(&mut self, merge: merge::Merge, report: &mut SyncReport) -> Result<()> {
+        for task in merge.update {
+            self.inner.update_task(task.id.unwrap(), task)?;
+            report.updated += 1;
+        }
+        for id in merge.remove {
+            self.inner.remove(id)?;
+            report.removed += 1;
+        }
+        for task in merge.add {
+            self.inner.write_task(task)?;
+            report.added += 1;
+        }
+        for dep in merge.add_dependencies {
+            self.inner.add_dependency(dep)?;
+        }
+        for dep in merge.remove_dependencies {
+            self.inner.remove_dependency(dep)?;
+        }
+        for session in merge.add_sessions {
+            self.inner.add_session(session)?;
+        }
+        report.conflicts = merge.conflicts;
+        Ok(())
+    }
+}
+
+impl Datasource for GitDataSource {
+    fn start(&self) -> Result<()> {
+        log::info!(
+            "versioning datasource in git at: {}",
+            self.dir.to_string_lossy()
+        );
+        self.inner.start()
+    }
+
+    fn stop(&self) -> Result<bool> {
+        self.inner.stop()
+    }
+
+    fn remove(&mut self, id: u64) -> Result<usize> {
+        let res = self.inner.remove(id)?;
+        self.commit(&format!("remove task {}", id))?;
+        Ok(res)
+    }
+
+    fn load_task(&self) -> Result<Task> {
+        self.inner.load_task()
+    }
+
+    fn write_task(&mut self, task: Task) -> Result<usize> {
+        let message = match task.id {
+            Some(id) => format!("add task {} \"{}\"", id, task.short),
+            None => format!("add task \"{}\"", task.short),
+        };
+        let res = self.inner.write_task(task)?;
+        self.commit(&message)?;
+        Ok(res)
+    }
+
+    fn list(&self, tasks: &mut Vec<Task>, filter: &Option<(FilterOptions, String)>) -> Result<()> {
+        self.inner.list(tasks, filter)
+    }
+
+    fn list_sorted(
+        &self,
+        tasks: &mut Vec<Task>,
+        filter: &Option<(FilterOptions, String)>,
+        keys: &[SortKey],
+    ) -> Result<()> {
+        self.inner.list_sorted(tasks, filter, keys)
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        self.inner.search(query)
+    }
+
+    fn get(&self, id: u64) -> Result<Task> {
+        self.inner.get(id)
+    }
+
+    fn find_by_uuid(&self, prefix: &str) -> Result<Vec<Task>> {
+        self.inner.find_by_uuid(prefix)
+    }
+
+    fn update_task(&mut self, id: u64, task: Task) -> Result<usize> {
+        let message = match self.inner.get(id) {
+            Ok(old) => describe_update(id, &old, &task),
+            Err(_) => format!("update task {}", id),
+        };
+        let res = self.inner.update_task(id, task)?;
+        self.commit(&message)?;
+        Ok(res)
+    }
+
+    // working ids are only ui state, not worth a commit
+    fn save_working_ids(&mut self, uuids: &[Uuid]) -> Result<()> {
+        self.inner.save_working_ids(uuids)
+    }
+
+    fn working_ids(&self) -> Result<Vec<Uuid>> {
+        self.inner.working_ids()
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
+        self.inner.setting(key)
+    }
+
+    fn save_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        self.inner.save_setting(key, value)?;
+        self.commit(&format!("set {} to {}", key, value))
+    }
+
+    fn settings(&self) -> Result<BTreeMap<String, String>> {
+        self.inner.settings()
+    }
+
+    fn add_dependency(&mut self, dep: Dependency) -> Result<usize> {
+        let message = format!(
+            "{} blocked by {}",
+            self.label(&dep.task),
+            self.label(&dep.blocker)
+        );
+        let res = self.inner.add_dependency(dep)?;
+        self.commit(&message)?;
+        Ok(res)
+    }
+
+    fn remove_dependency(&mut self, dep: Dependency) -> Result<usize> {
+        let message = format!(
+            "{} no longer blocked by {}",
+            self.label(&dep.task),
+            self.label(&dep.blocker)
+        );
+        let res = self.inner.remove_dependency(dep)?;
+        self.commit(&message)?;
+        Ok(res)
+    }
+
+    fn dependencies(&self) -> Result<Vec<Dependency>> {
+        self.inner.dependencies()
+    }
+
+    fn tombstones(&self) -> Result<Vec<Tombstone>> {
+        self.inner.tombstones()
+    }
+
+    fn save_tombstones(&mut self, tombstones: &[Tombstone]) -> Result<()> {
+        self.inner.save_tombstones(tombstones)?;
+        self.commit(&format!("keep {} removed tasks", tombstones.len()))
+    }
+
+    fn add_session(&mut self, session: Session) -> Result<usize> {
+        let message = match session.end {
+            Some(_) => format!("log work on {}", self.label(&session.task)),
+            None => format!("start work on {}", self.label(&session.task)),
+        };
+        let res = self.inner.add_session(session)?;
+        self.commit(&message)?;
+        Ok(res)
+    }
+
+    fn end_sessions(&mut self, at: Timestamp) -> Result<usize> {
+        let res = self.inner.end_sessions(at)?;
+        if res > 0 {
+            self.commit("stop work")?;
+        }
+        Ok(res)
+    }
+
+    fn sessions(&self) -> Result<Vec<Session>> {
+        self.inner.sessions()
+    }
+
+    fn sync(&mut self, remote: &str, policy: MergePolicy) -> Result<SyncReport> {
+        let head = self.git(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+        let branch = String::from_utf8_lossy(&head).trim().to_string();
+        self.git(&["fetch", "-q", remote, &branch])?;
+
+        let mut report = SyncReport::default();
+        if self
+            .git(&["merge-base", "--is-ancestor", "FETCH_HEAD", "HEAD"])
+            .is_ok()
+        {
+            log::info!("already up to date with {}", remote);
+            return Ok(report);
+        }
+        let (base, base_deps, _) = match self.git(&["merge-base", "HEAD", "FETCH_HEAD"]) {
+            Ok(rev) => self.snapshot(String::from_utf8_lossy(&rev).trim())?,
+            Err(_) => (vec![], vec![], vec![]),
+        };
+        let (theirs, their_deps, their_sessions) = self.snapshot("FETCH_HEAD")?;
+        let mut ours = vec![];
+        self.inner.list(&mut ours, &None)?;
+        let mut merge = merge::merge(&base, &ours, &theirs, policy);
+        merge::merge_dependencies(
+            &base_deps,
+            &self.inner.dependencies()?,
+            &their_deps,
+            &mut merge,
+        );
+        merge.add_sessions = merge::merge_sessions(&self.inner.sessions()?, &their_sessions);
+
+        // record the merge in git, but let the datasource itself merge the content
+        self.git(&[
+            "merge",
+            "-q",
+            "--no-ff",
+            "--no-commit",
+            "--allow-unrelated-histories",
+            "-s",
+            "ours",
+            "FETCH_HEAD",
+        ])?;
+        self.inner.begin_transaction()?;
+        if let Err(e) = self.apply(merge, &mut report) {
+            if let Err(rollback) = self.inner.rollback_transaction() {
+                log::error!("failed to roll back sync: {rollback:?}");
+            }
+            let _ = self.git(&["merge", "--abort"]);
+            return Err(e);
+        }
+        self.inner.commit_transaction()?;
+        self.inner.flush()?;
+        self.git(&["add", "--", &self.file])?;
+        self.git(&[
+            "commit",
+            "-q",
+            "-m",
+            &format!(
+                "sync with {}: {} added, {} updated, {} removed, {} conflicts",
+                remote,
+                report.added,
+                report.updated,
+                report.removed,
+                report.conflicts.len()
+            ),
+        ])?;
+        Ok(report)
+    }
+
+    fn begin_transaction(&mut self) -> Result<()> {
+        self.inner.begin_transaction()?;
+        self.savepoints.push(self.pending.len());
+        Ok(())
+    }
+
+    /// Once the outermost transaction is committed, all of its changes become one git commit.
+    fn commit_transaction(&mut self) -> Result<()> {
+        self.inner.commit_transaction()?;
+        self.savepoints.pop();
+        if !self.savepoints.is_empty() || self.pending.is_empty() {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let message = match pending.as_slice() {
+            [one] => one.clone(),
+            _ => format!("{} changes\n\n{}", pending.len(), pending.join("\n")),
+        };
+        self.commit(&message)
+    }
+
+    fn rollback_transaction(&mut self) -> Result<()> {
+        self.inner.rollback_transaction()?;
+        if let Some(pending) = self.savepoints.pop() {
+            self.pending.truncate(pending);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn describe_update(id: u64, old: &Task, new: &Task) -> String {
+    let mut changes = vec![];
+    if old.status != new.status {
+        changes.push(format!("status {}", new.status.to_string()));
+    }
+    if old.prio != new.prio {
+        changes.push(format!("priority {}", new.prio.to_string()));
+    }
+    if old.short != new.short {
+        changes.push(format!("name \"{}\"", new.short));
+    }
+    if old.desc != new.desc {
+        changes.push("description".to_string());
+    }
+    if old.parent != new.parent {
+        changes.push("parent".to_string());
+    }
+    if old.rank != new.rank {
+        match new.rank {
+            Some(rank) => changes.push(format!("position {}", rank)),
+            None => changes.push("unranked".to_string()),
+        }
+    }
+    if old.snoozed_until != new.snoozed_until {
+        match new.snoozed_until {
+            Some(until) => changes.push(format!("snoozed until {}", until.format("%Y-%m-%d"))),
+            None => changes.push("unsnoozed".to_string()),
+        }
+    }
+    if old.due != new.due {
+        match new.due {
+            Some(due) => changes.push(format!("due {}", due.format("%Y-%m-%d"))),
+            None => changes.push("not due".to_string()),
+        }
+    }
+    if old.recurrence != new.recurrence {
+        match new.recurrence {
+            Some(rule) => changes.push(format!("recurring {}", rule)),
+            None => changes.push("not recurring".to_string()),
+        }
+    }
+    if old.estimate != new.estimate {
+        match new.estimate {
+            Some(estimate) => changes.push(format!("estimate {}", estimate)),
+            None => changes.push("no estimate".to_string()),
+        }
+    }
+    if changes.is_empty() {
+        return format!("update task {}", id);
+    }
+    format!("set task {} {}", id, changes.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{TaskPriority, TaskStatus};
+
+    fn open_git(path: &Path) -> Result<GitDataSource> {
+        let path = path.to_string_lossy().to_string();
+        let inner = source::open(Datasources::SqlLite, &path)?;
+        GitDataSource::new(inner, Datasources::SqlLite, &path)
+    }
+
+    fn git_in(dir: &Path, args: &[&str]) -> String {
+        let out = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .expect("git should be installed");
+        assert!(out.status.success());
+        String::from_utf8_lossy(&out.stdout).to_string()
+    }
+
+    /// Commits in tests need an identity, set in the repository so the environment is left alone.
+    fn set_identity(dir: &Path) {
+        git_in(dir, &["config", "user.name", "myprio"]);
+        git_in(dir, &["config", "user.email", "myprio@localhost"]);
+    }
+
+    #[test]
+    fn commits_and_syncs() -> Result<()> {
+        let ours_dir = tempfile::tempdir()?;
+        let theirs_dir = tempfile::tempdir()?;
+        git_in(ours_dir.path(), &["init", "-q"]);
+        set_identity(ours_dir.path());
+        let mut ours = open_git(&ours_dir.path().join("tasks.sql"))?;
+        ours.write_task(Task::new(
+            "report".to_string(),
+            "weekly".to_string(),
+            TaskPriority::ToDo,
+        ))?;
+        let mut task = ours.get(1)?;
+        task.status = TaskStatus::Started;
+        ours.update_task(1, task)?;
+        let log = git_in(ours_dir.path(), &["log", "--format=%s"]);
+        assert!(log.starts_with("set task 1 status Started\nadd task \"report\"\n"));
+
+        git_in(
+            theirs_dir.path(),
+            &["clone", "-q", &ours_dir.path().to_string_lossy(), "."],
+        );
+        set_identity(theirs_dir.path());
+        let mut theirs = open_git(&theirs_dir.path().join("tasks.sql"))?;
+        let mut task = theirs.get(1)?;
+        task.status = TaskStatus::Completed;
+        task.prio = TaskPriority::UrgentAndImportant;
+        theirs.update_task(1, task)?;
+
+        // concurrent edit of status, prio only changed by them
+        let mut task = ours.get(1)?;
+        task.status = TaskStatus::WontDo;
+        ours.update_task(1, task)?;
+
+        let report = ours.sync(&theirs_dir.path().to_string_lossy(), MergePolicy::Ours)?;
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].fields, vec!["status"]);
+        let task = ours.get(1)?;
+        assert_eq!(task.status, TaskStatus::WontDo);
+        assert_eq!(task.prio, TaskPriority::UrgentAndImportant);
+
+        // the other side fast forwards to the merge and keeps our resolution
+        let report = theirs.sync(&ours_dir.path().to_string_lossy(), MergePolicy::Theirs)?;
+        assert!(report.conflicts.is_empty());
+        assert_eq!(theirs.get(1)?.status, TaskStatus::WontDo);
+        Ok(())
+    }
+}
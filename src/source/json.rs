@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use log;
+use serde::{Deserialize, Serialize};
+
+use crate::app::FilterOptions;
+use crate::error::{Error, Result};
+use crate::source::Datasource;
+use crate::task::deps::Dependency;
+use crate::task::merge::{MergePolicy, SyncReport, Tombstone};
+use crate::task::time::Session;
+use crate::task::{Task, Timestamp};
+use uuid::Uuid;
+
+/// Keeps all tasks in a single, human readable json file which is rewritten on every change.
+pub struct JsonDataSource {
+    path: String,
+    store: JsonStore,
+    /// the store as it was when each open transaction began, innermost last. The file is
+    /// only written once the outermost one is committed.
+    savepoints: Vec<JsonStore>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct JsonStore {
+    tasks: Vec<Task>,
+    /// highest id ever handed out, so ids of removed tasks are not reused
+    #[serde(default)]
+    last_id: u64,
+    #[serde(default)]
+    working_ids: Vec<Uuid>,
+    #[serde(default)]
+    dependencies: Vec<Dependency>,
+    #[serde(default)]
+    sessions: Vec<Session>,
+    #[serde(default)]
+    tombstones: Vec<Tombstone>,
+    #[serde(default)]
+    settings: BTreeMap<String, String>,
+}
+
+impl JsonDataSource {
+    pub fn new(path: &String) -> Result<Self> {
+        log::debug!("json datasource path: {}", path);
+        let store = if Path::new(path).exists() && fs::metadata(path)?.len() > 0 {
+            serde_json::from_str(&fs::read_to_string(path)?)?
+        } else {
+            JsonStore::default()
+        };
+        // the file is only written once something changes
+        Ok(Self {
+            path: path.clone(),
+            store,
+            savepoints: vec![],
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        if !self.savepoints.is_empty() {
+            return Ok(());
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.store)?)?;
+        Ok(())
+    }
+
+    fn next_id(&self) -> u64 {
+        self.store
+            .tasks
+            .iter()
+            .filter_map(|t| t.id)
+            .max()
+            .unwrap_or(0)
+            .max(self.store.last_id)
+            + 1
+    }
+
+    fn position(&self, id: u64) -> Option<usize> {
+        self.store.tasks.iter().position(|t| t.id == Some(id))
+    }
+}
+
+impl Datasource for JsonDataSource {
+    fn start(&self) -> Result<()> {
+        log::info!("running with datasource at: {}", self.path);
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<bool> {
+        todo!()
+    }
+
+    fn load_task(&self) -> Result<Task> {
+        todo!()
+    }
+
+    fn write_task(&mut self, mut task: Task) -> Result<usize> {
+        match task.id {
+            Some(id) if self.position(id).is_some() => {
+                return Err(Error::Conflict(format!("task {} already exists", id)))
+            }
+            Some(_) => {}
+            None => task.id = Some(self.next_id()),
+        }
+        self.store.last_id = self.store.last_id.max(task.id.unwrap());
+        self.store.tasks.push(task);
+        self.save()?;
+        Ok(1)
+    }
+
+    fn remove(&mut self, id: u64) -> Result<usize> {
+        let before = self.store.tasks.len();
+        if let Some(pos) = self.position(id) {
+            let uuid = self.store.tasks[pos].uuid;
+            self.store
+                .dependencies
+                .retain(|d| d.task != uuid && d.blocker != uuid);
+            self.store.sessions.retain(|s| s.task != uuid);
+            self.store.tombstones.retain(|r| r.uuid != uuid);
+            self.store.tombstones.push(Tombstone {
+                uuid,
+                removed: Utc::now(),
+            });
+        }
+        self.store.tasks.retain(|t| t.id != Some(id));
+        self.save()?;
+        Ok(before - self.store.tasks.len())
+    }
+
+    fn list(&self, tasks: &mut Vec<Task>, filter: &Option<(FilterOptions, String)>) -> Result<()> {
+        for t in &self.store.tasks {
+            let keep = match filter {
+                None => true,
+                Some((FilterOptions::ByName, val)) => t.short.contains(val.as_str()),
+                Some((FilterOptions::ByStatus, val)) => &t.status.to_string() == val,
+                Some((FilterOptions::ByPriority, val)) => &t.prio.to_string() == val,
+                Some((FilterOptions::ByGroup, _)) => false,
+            };
+            if keep {
+                tasks.push(t.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, id: u64) -> Result<Task> {
+        match self.position(id) {
+            Some(pos) => Ok(self.store.tasks[pos].clone()),
+            None => Err(Error::not_found(id)),
+        }
+    }
+
+    fn find_by_uuid(&self, prefix: &str) -> Result<Vec<Task>> {
+        Ok(self
+            .store
+            .tasks
+            .iter()
+            .filter(|t| t.uuid.to_string().starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    fn update_task(&mut self, id: u64, mut task: Task) -> Result<usize> {
+        let pos = match self.position(id) {
+            Some(pos) => pos,
+            None => return Ok(0),
+        };
+        task.id = Some(id);
+        self.store.tasks[pos] = task;
+        self.save()?;
+        Ok(1)
+    }
+
+    fn save_working_ids(&mut self, uuids: &[Uuid]) -> Result<()> {
+        self.store.working_ids = uuids.to_vec();
+        self.save()
+    }
+
+    fn working_ids(&self) -> Result<Vec<Uuid>> {
+        Ok(self.store.working_ids.clone())
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.store.settings.get(key).cloned())
+    }
+
+    fn save_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        self.store
+            .settings
+            .insert(key.to_string(), value.to_string());
+        self.save()
+    }
+
+    fn settings(&self) -> Result<BTreeMap<String, String>> {
+        Ok(self.store.settings.clone())
+    }
+
+    fn add_dependency(&mut self, dep: Dependency) -> Result<usize> {
+        if self.store.dependencies.contains(&dep) {
+            return Ok(0);
+        }
+        self.store.dependencies.push(dep);
+        self.save()?;
+        Ok(1)
+    }
+
+    fn remove_dependency(&mut self, dep: Dependency) -> Result<usize> {
+        let before = self.store.dependencies.len();
+        self.store.dependencies.retain(|d| *d != dep);
+        self.save()?;
+        Ok(before - self.store.dependencies.len())
+    }
+
+    fn dependencies(&self) -> Result<Vec<Dependency>> {
+        Ok(self.store.dependencies.clone())
+    }
+
+    fn add_session(&mut self, session: Session) -> Result<usize> {
+        self.store
+            .sessions
+            .retain(|s| s.task != session.task || s.start != session.start);
+        self.store.sessions.push(session);
+        self.store.sessions.sort_by_key(|s| s.start);
+        self.save()?;
+        Ok(1)
+    }
+
+    fn end_sessions(&mut self, at: Timestamp) -> Result<usize> {
+        let mut ended = 0;
+        for s in self.store.sessions.iter_mut().filter(|s| s.end.is_none()) {
+            s.end = Some(at);
+            ended += 1;
+        }
+        self.save()?;
+        Ok(ended)
+    }
+
+    fn sessions(&self) -> Result<Vec<Session>> {
+        Ok(self.store.sessions.clone())
+    }
+
+    fn tombstones(&self) -> Result<Vec<Tombstone>> {
+        Ok(self.store.tombstones.clone())
+    }
+
+    fn save_tombstones(&mut self, tombstones: &[Tombstone]) -> Result<()> {
+        self.store.tombstones = tombstones.to_vec();
+        self.save()
+    }
+
+    fn sync(&mut self, _remote: &str, _policy: MergePolicy) -> Result<SyncReport> {
+        Err(Error::InvalidInput(
+            "the json datasource is not versioned, run with --git to sync".to_string(),
+        ))
+    }
+
+    fn begin_transaction(&mut self) -> Result<()> {
+        self.savepoints.push(self.store.clone());
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        if self.savepoints.pop().is_none() {
+            return Err(Error::storage("no transaction to commit"));
+        }
+        self.save()
+    }
+
+    fn rollback_transaction(&mut self) -> Result<()> {
+        match self.savepoints.pop() {
+            Some(store) => self.store = store,
+            None => return Err(Error::storage("no transaction to roll back")),
+        }
+        Ok(())
+    }
+}
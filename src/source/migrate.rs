@@ -0,0 +1,271 @@
+use std::hash::{DefaultHasher, Hasher};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use home;
+use log;
+
+use crate::app::Datasources;
+use crate::error::{Error, Result};
+use crate::source::{self, Datasource};
+use crate::task::Task;
+
+/// A datasource given on the command line as `<kind>:<path>`, e.g. `sqllite:~/mytasks.sql`.
+#[derive(Debug, Clone)]
+pub struct DatasourceSpec {
+    pub datasource: Datasources,
+    pub path: String,
+}
+
+impl FromStr for DatasourceSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (kind, path) = match s.split_once(':') {
+            Some(parts) => parts,
+            None => return Err(format!("expected <kind>:<path>, got {}", s)),
+        };
+        let datasource = Datasources::from_str(kind, true)?;
+        let path = match (path.strip_prefix("~/"), home::home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+            _ => path.to_string(),
+        };
+        Ok(Self { datasource, path })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MigrationReport {
+    pub tasks: usize,
+    pub checksum: u64,
+}
+
+/// Copies every task from `from` into `to`, keeping ids, and verifies the copy afterwards.
+/// A target which already contains tasks is only overwritten with `force`. The target is
+/// left as it was if anything fails.
+pub fn migrate(
+    from: &dyn Datasource,
+    to: &mut dyn Datasource,
+    force: bool,
+) -> Result<MigrationReport> {
+    source::with_transaction(to, |to| copy(from, to, force))
+}
+
+fn copy(from: &dyn Datasource, to: &mut dyn Datasource, force: bool) -> Result<MigrationReport> {
+    let mut existing = vec![];
+    to.list(&mut existing, &None)?;
+    if !existing.is_empty() {
+        if !force {
+            return Err(Error::Conflict(format!(
+                "target already contains {} tasks, use --force to overwrite them",
+                existing.len()
+            )));
+        }
+        log::info!("removing {} tasks from target", existing.len());
+        for t in existing {
+            to.remove(t.id.unwrap())?;
+        }
+    }
+
+    let mut tasks = vec![];
+    from.list(&mut tasks, &None)?;
+    for t in &tasks {
+        to.write_task(t.clone())?;
+    }
+    let deps = from.dependencies()?;
+    for dep in &deps {
+        to.add_dependency(*dep)?;
+    }
+    let sessions = from.sessions()?;
+    for session in &sessions {
+        to.add_session(*session)?;
+    }
+    // replaces the tombstones of the tasks removed above, they are added again
+    let tombstones = from.tombstones()?;
+    to.save_tombstones(&tombstones)?;
+    let settings = from.settings()?;
+    for (key, value) in &settings {
+        to.save_setting(key, value)?;
+    }
+
+    let mut copied = vec![];
+    to.list(&mut copied, &None)?;
+    let expected = checksum(&mut tasks);
+    let actual = checksum(&mut copied);
+    let mut copied_deps = to.dependencies()?;
+    copied_deps.sort();
+    let mut expected_deps = deps;
+    expected_deps.sort();
+    if copied_deps != expected_deps {
+        return Err(Error::storage(format!(
+            "verification failed: expected {} dependencies, target has {}",
+            expected_deps.len(),
+            copied_deps.len()
+        )));
+    }
+    let mut copied_sessions = to.sessions()?;
+    copied_sessions.sort_by_key(|s| (s.start, s.task));
+    let mut expected_sessions = sessions;
+    expected_sessions.sort_by_key(|s| (s.start, s.task));
+    if copied_sessions != expected_sessions {
+        return Err(Error::storage(format!(
+            "verification failed: expected {} work sessions, target has {}",
+            expected_sessions.len(),
+            copied_sessions.len()
+        )));
+    }
+    let mut copied_tombstones = to.tombstones()?;
+    copied_tombstones.sort_by_key(|r| r.uuid);
+    let mut expected_tombstones = tombstones;
+    expected_tombstones.sort_by_key(|r| r.uuid);
+    if copied_tombstones != expected_tombstones {
+        return Err(Error::storage(format!(
+            "verification failed: expected {} removed tasks, target has {}",
+            expected_tombstones.len(),
+            copied_tombstones.len()
+        )));
+    }
+    let copied_settings = to.settings()?;
+    if settings
+        .iter()
+        .any(|(k, v)| copied_settings.get(k) != Some(v))
+    {
+        return Err(Error::storage(format!(
+            "verification failed: expected {} settings, target has {}",
+            settings.len(),
+            copied_settings.len()
+        )));
+    }
+    if tasks.len() != copied.len() || expected != actual {
+        return Err(Error::storage(format!(
+            "verification failed: expected {} tasks with checksum {:x}, target has {} with checksum {:x}",
+            tasks.len(),
+            expected,
+            copied.len(),
+            actual
+        )));
+    }
+    Ok(MigrationReport {
+        tasks: copied.len(),
+        checksum: actual,
+    })
+}
+
+/// Opens both datasources and runs `migrate` between them. The source has to exist, so a
+/// mistyped path doesn't empty the target.
+pub fn run(from: &DatasourceSpec, to: &DatasourceSpec, force: bool) -> Result<MigrationReport> {
+    let source = source::open_existing(from.datasource, &from.path)?;
+    let mut target = source::open(to.datasource, &to.path)?;
+    migrate(source.as_ref(), target.as_mut(), force)
+}
+
+fn checksum(tasks: &mut [Task]) -> u64 {
+    tasks.sort_by_key(|t| t.id);
+    let mut hasher = DefaultHasher::new();
+    for t in tasks.iter() {
+        hasher.write(
+            format!(
+                "{:?}|{}|{}|{}|{}|{:?}|{}|{}|{}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}\n",
+                t.id,
+                t.uuid,
+                t.short,
+                t.desc,
+                t.created.to_rfc3339(),
+                t.started.map(|s| s.to_rfc3339()),
+                t.status.to_string(),
+                t.prio.to_string(),
+                t.modified.to_rfc3339(),
+                t.parent,
+                t.rank,
+                t.snoozed_until.map(|s| s.to_rfc3339()),
+                t.recurrence.map(|r| r.to_string()),
+                t.series,
+                t.estimate.map(|e| e.to_string()),
+                t.completed.map(|c| c.to_rfc3339()),
+                t.due.map(|d| d.to_rfc3339())
+            )
+            .as_bytes(),
+        );
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::deps::Dependency;
+    use crate::task::{TaskPriority, TaskStatus};
+    use std::fs;
+
+    #[test]
+    fn sqllite_to_json() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let from: DatasourceSpec = format!("sqllite:{}", dir.path().join("t.sql").display())
+            .parse()
+            .unwrap();
+        let to: DatasourceSpec = format!("json:{}", dir.path().join("t.json").display())
+            .parse()
+            .unwrap();
+        assert_eq!(to.datasource, Datasources::Json);
+
+        let mut sql = source::open(from.datasource, &from.path)?;
+        for name in ["one", "two", "three"] {
+            sql.write_task(Task::new(
+                name.to_string(),
+                "desc".to_string(),
+                TaskPriority::Watch,
+            ))?;
+        }
+        sql.remove(2)?;
+        let mut started = sql.get(3)?;
+        started.status = TaskStatus::Started;
+        sql.update_task(3, started)?;
+        sql.add_dependency(Dependency {
+            task: sql.get(3)?.uuid,
+            blocker: sql.get(1)?.uuid,
+        })?;
+        sql.save_setting("review.thresholds", "Watch=3")?;
+
+        let report = run(&from, &to, false)?;
+        assert_eq!(report.tasks, 2);
+        let json = source::open(to.datasource, &to.path)?;
+        assert_eq!(json.get(3)?, sql.get(3)?);
+        assert!(json.get(2).is_err());
+        assert_eq!(json.dependencies()?, sql.dependencies()?);
+        assert_eq!(json.tombstones()?, sql.tombstones()?);
+        assert_eq!(json.settings()?, sql.settings()?);
+
+        // target is not empty anymore
+        assert!(run(&from, &to, false).is_err());
+        assert_eq!(run(&from, &to, true)?, report);
+        // tasks removed to overwrite the target are back, so they have no tombstones
+        let json = source::open(to.datasource, &to.path)?;
+        assert_eq!(json.tombstones()?, sql.tombstones()?);
+        assert_eq!(json.tombstones()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_source() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let to: DatasourceSpec = format!("json:{}", dir.path().join("t.json").display())
+            .parse()
+            .unwrap();
+        let mut json = source::open(to.datasource, &to.path)?;
+        json.write_task(Task::new(
+            "keep me".to_string(),
+            "".to_string(),
+            TaskPriority::ToDo,
+        ))?;
+        let before = fs::read_to_string(&to.path)?;
+
+        for kind in ["sqllite", "json"] {
+            let missing = dir.path().join("typo");
+            let from: DatasourceSpec = format!("{}:{}", kind, missing.display()).parse().unwrap();
+            assert!(matches!(run(&from, &to, true), Err(Error::InvalidInput(_))));
+            assert!(!missing.exists());
+        }
+        assert_eq!(fs::read_to_string(&to.path)?, before);
+        Ok(())
+    }
+}
@@ -1,16 +1,133 @@
+pub mod git;
+pub mod json;
+pub mod migrate;
 pub mod sqllite;
 
-use crate::app::FilterOptions;
-use crate::error::Result;
-use crate::task::Task;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::app::{Datasources, FilterOptions};
+use crate::error::{Error, Result};
+use crate::task::deps::Dependency;
+use crate::task::merge::{MergePolicy, SyncReport, Tombstone};
+use crate::task::search::{self, Hit};
+use crate::task::sort::{self, SortKey};
+use crate::task::time::Session;
+use crate::task::{Task, Timestamp};
+use uuid::Uuid;
 
 pub trait Datasource {
     fn start(&self) -> Result<()>;
     fn stop(&self) -> Result<bool>;
+    /// Removes a task, leaving a `Tombstone` behind.
     fn remove(&mut self, id: u64) -> Result<usize>;
     fn load_task(&self) -> Result<Task>;
     fn write_task(&mut self, task: Task) -> Result<usize>;
     fn list(&self, tasks: &mut Vec<Task>, filter: &Option<(FilterOptions, String)>) -> Result<()>;
+    /// Like `list`, sorted by `keys`. Datasources which can't sort themselves get the
+    /// tasks sorted in memory.
+    fn list_sorted(
+        &self,
+        tasks: &mut Vec<Task>,
+        filter: &Option<(FilterOptions, String)>,
+        keys: &[SortKey],
+    ) -> Result<()> {
+        let from = tasks.len();
+        self.list(tasks, filter)?;
+        sort::sort(&mut tasks[from..], keys);
+        Ok(())
+    }
+    /// Tasks whose name or description match `query`, best matches first. Datasources
+    /// without a search index search the tasks in memory.
+    fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        let mut all = vec![];
+        self.list(&mut all, &None)?;
+        Ok(search::search(&all, query))
+    }
     fn get(&self, id: u64) -> Result<Task>;
+    /// All tasks whose uuid starts with `prefix` (lowercase, hyphenated form).
+    fn find_by_uuid(&self, prefix: &str) -> Result<Vec<Task>>;
     fn update_task(&mut self, id: u64, task: Task) -> Result<usize>;
+    /// Remembers which task was shown under which working id, in order starting at 1.
+    fn save_working_ids(&mut self, uuids: &[Uuid]) -> Result<()>;
+    fn working_ids(&self) -> Result<Vec<Uuid>>;
+    /// A setting of the user kept with the tasks, e.g. the review thresholds.
+    fn setting(&self, key: &str) -> Result<Option<String>>;
+    fn save_setting(&mut self, key: &str, value: &str) -> Result<()>;
+    fn settings(&self) -> Result<BTreeMap<String, String>>;
+    fn add_dependency(&mut self, dep: Dependency) -> Result<usize>;
+    fn remove_dependency(&mut self, dep: Dependency) -> Result<usize>;
+    fn dependencies(&self) -> Result<Vec<Dependency>>;
+    /// Adds a work session, or replaces the one of the same task with the same start.
+    fn add_session(&mut self, session: Session) -> Result<usize>;
+    /// Ends all running work sessions at `at`.
+    fn end_sessions(&mut self, at: Timestamp) -> Result<usize>;
+    fn sessions(&self) -> Result<Vec<Session>>;
+    /// The tasks removed so far.
+    fn tombstones(&self) -> Result<Vec<Tombstone>>;
+    /// Replaces the tombstones, e.g. with the ones of the datasource this one is a copy of.
+    fn save_tombstones(&mut self, tombstones: &[Tombstone]) -> Result<()>;
+    fn sync(&mut self, remote: &str, policy: MergePolicy) -> Result<SyncReport>;
+    /// Starts a transaction: the changes up to the matching `commit_transaction` are kept
+    /// together, or dropped with `rollback_transaction`. Transactions can be nested.
+    fn begin_transaction(&mut self) -> Result<()>;
+    fn commit_transaction(&mut self) -> Result<()>;
+    fn rollback_transaction(&mut self) -> Result<()>;
+    /// Writes pending changes into the datasource file itself, e.g. before it is committed to git.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `f` in a transaction on `source`, which is committed if `f` succeeds and rolled
+/// back if it fails.
+pub fn with_transaction<T>(
+    source: &mut dyn Datasource,
+    f: impl FnOnce(&mut dyn Datasource) -> Result<T>,
+) -> Result<T> {
+    transaction_of(source, |source| source, |source| f(source))
+}
+
+/// Like `with_transaction`, for anything `owner` which holds a datasource, given by `source`.
+pub fn transaction_of<O: ?Sized, T>(
+    owner: &mut O,
+    source: impl for<'a> Fn(&'a mut O) -> &'a mut (dyn Datasource + 'a),
+    f: impl FnOnce(&mut O) -> Result<T>,
+) -> Result<T> {
+    source(owner).begin_transaction()?;
+    match f(owner) {
+        Ok(res) => {
+            source(owner).commit_transaction()?;
+            Ok(res)
+        }
+        Err(e) => {
+            if let Err(rollback) = source(owner).rollback_transaction() {
+                log::error!("failed to roll back transaction: {rollback:?}");
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Opens the existing datasource of the given kind at `path` for reading only, unlike `open`
+/// which creates a missing one.
+pub fn open_existing(datasource: Datasources, path: &String) -> Result<Box<dyn Datasource>> {
+    if !Path::new(path).is_file() {
+        return Err(Error::InvalidInput(format!(
+            "there is no datasource at {}",
+            path
+        )));
+    }
+    Ok(match datasource {
+        Datasources::SqlLite => Box::new(sqllite::SqlLiteDataSource::open_read_only(path)?),
+        Datasources::Json => Box::new(json::JsonDataSource::new(path)?),
+    })
+}
+
+/// Opens the file based datasource of the given kind at `path`.
+pub fn open(datasource: Datasources, path: &String) -> Result<Box<dyn Datasource>> {
+    Ok(match datasource {
+        Datasources::SqlLite => Box::new(sqllite::SqlLiteDataSource::new(path)?),
+        Datasources::Json => Box::new(json::JsonDataSource::new(path)?),
+    })
 }
@@ -1,32 +1,231 @@
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
 use log;
 use rusqlite::{
-    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
-    Connection, Row, ToSql,
+    params,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Type, ValueRef},
+    Connection, ErrorCode, OpenFlags, OptionalExtension, Row, ToSql, TransactionBehavior,
 };
+use uuid::Uuid;
 
+use crate::error::Error;
 use crate::source::Datasource;
-use crate::task::{Task, TaskPriority, TaskStatus};
+use crate::task::deps::Dependency;
+use crate::task::merge::{MergePolicy, SyncReport, Tombstone};
+use crate::task::search::{self, Hit};
+use crate::task::sort::{self, SortKey};
+use crate::task::time::Session;
+use crate::task::{Task, TaskPriority, TaskStatus, Timestamp};
 use crate::{app::FilterOptions, error::Result};
 
 pub struct SqlLiteDataSource {
     settings: SqlLiteSettings,
     conn: rusqlite::Connection,
+    /// open transactions, the outermost one is a real transaction and the others savepoints
+    depth: usize,
 }
 
 pub struct SqlLiteSettings {
     path: String,
 }
 
+/// Columns in the order expected by `row_to_task`.
+const TASK_COLUMNS: &str =
+    "rowid,short,desc,status,created,started,prio,uuid,modified,parent,rank,snoozed_until,recurrence,series,estimate,completed,due";
+
+/// Schema changes applied to existing databases, `PRAGMA user_version` holds how many have run.
+const SCHEMA_VERSION: i64 = 14;
+
+/// How long sqlite itself waits for a lock held by another myprio before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Retries of a write sqlite gave up on, each waiting twice as long as the one before.
+const BUSY_RETRIES: u32 = 5;
+const BUSY_BACKOFF: Duration = Duration::from_millis(50);
+
 impl SqlLiteDataSource {
     pub fn new(path: &String) -> Result<Self> {
         let settings = SqlLiteSettings { path: path.clone() };
         log::debug!("SqlLite database path: {}", path);
-        let conn = Connection::open(path)?;
+        let mut conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // readers don't block the writer and the other way round, for several terminals at once
+        retry(|| conn.pragma_update(None, "journal_mode", "WAL"))?;
         log::debug!("creating new database!");
         let table = "CREATE TABLE IF NOT EXISTS tasks(short TEXT, desc TEXT, status TEXT, created TEXT, started TEXT, prio TEXT);";
-        conn.execute(table, ())?;
-        Ok(Self { settings, conn })
+        retry(|| conn.execute(table, ()))?;
+        migrate_schema(&mut conn)?;
+        Ok(Self {
+            settings,
+            conn,
+            depth: 0,
+        })
+    }
+
+    /// Opens an existing database without changing it, e.g. as the source of a migration.
+    /// Databases of an older schema have to be upgraded by opening them with `new` first.
+    pub fn open_read_only(path: &String) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < SCHEMA_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "{} uses an older schema, open it with myprio once to upgrade it",
+                path
+            )));
+        }
+        Ok(Self {
+            settings: SqlLiteSettings { path: path.clone() },
+            conn,
+            depth: 0,
+        })
+    }
+}
+
+/// Whether `err` is about a lock held by another connection, which goes away by waiting.
+fn is_busy(err: &rusqlite::Error) -> bool {
+    matches!(
+        err.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Runs `f` again while the database is busy. The busy timeout covers most waits, but sqlite
+/// fails right away where waiting could deadlock, e.g. when two transactions want to write.
+fn retry<T>(mut f: impl FnMut() -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+    let mut wait = BUSY_BACKOFF;
+    for _ in 0..BUSY_RETRIES {
+        match f() {
+            Err(e) if is_busy(&e) => {
+                log::warn!("database is busy, trying again in {:?}", wait);
+                thread::sleep(wait);
+                wait *= 2;
+            }
+            result => return result,
+        }
+    }
+    f()
+}
+
+fn migrate_schema(conn: &mut Connection) -> Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+    // another myprio may be migrating at the same time, take the write lock before looking again
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let version: i64 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+    log::info!(
+        "migrating database schema from version {} to {}",
+        version,
+        SCHEMA_VERSION
+    );
+    if version < 1 {
+        tx.execute("ALTER TABLE tasks ADD COLUMN uuid TEXT", ())?;
+        tx.execute("ALTER TABLE tasks ADD COLUMN modified TEXT", ())?;
+        let ids: Vec<u64> = tx
+            .prepare("SELECT rowid from tasks")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for id in ids {
+            tx.execute(
+                "UPDATE tasks SET uuid=?1, modified=created where rowid=?2",
+                (Uuid::new_v4().to_string(), id),
+            )?;
+        }
+        tx.execute("CREATE UNIQUE INDEX tasks_uuid ON tasks(uuid)", ())?;
+    }
+    if version < 2 {
+        // an INTEGER PRIMARY KEY keeps ids stable across VACUUM, AUTOINCREMENT never reuses them
+        tx.execute_batch(
+            "CREATE TABLE tasks_v2(id INTEGER PRIMARY KEY AUTOINCREMENT, short TEXT, desc TEXT, status TEXT, created TEXT, started TEXT, prio TEXT, uuid TEXT, modified TEXT);
+            INSERT INTO tasks_v2 (id,short,desc,status,created,started,prio,uuid,modified) SELECT rowid,short,desc,status,created,started,prio,uuid,modified from tasks;
+            DROP TABLE tasks;
+            ALTER TABLE tasks_v2 RENAME TO tasks;
+            CREATE UNIQUE INDEX tasks_uuid ON tasks(uuid);
+            CREATE TABLE working_ids(pos INTEGER PRIMARY KEY, uuid TEXT);",
+        )?;
+    }
+    if version < 3 {
+        tx.execute("ALTER TABLE tasks ADD COLUMN parent TEXT", ())?;
+    }
+    if version < 4 {
+        tx.execute(
+            "CREATE TABLE dependencies(task TEXT, blocker TEXT, UNIQUE(task, blocker))",
+            (),
+        )?;
+    }
+    if version < 5 {
+        tx.execute("ALTER TABLE tasks ADD COLUMN rank INTEGER", ())?;
+    }
+    if version < 6 {
+        tx.execute("ALTER TABLE tasks ADD COLUMN snoozed_until TEXT", ())?;
+    }
+    if version < 7 {
+        tx.execute("ALTER TABLE tasks ADD COLUMN recurrence TEXT", ())?;
+        tx.execute("ALTER TABLE tasks ADD COLUMN series TEXT", ())?;
+    }
+    if version < 8 {
+        tx.execute(
+            "CREATE TABLE sessions(task TEXT NOT NULL, start TEXT NOT NULL, end TEXT, UNIQUE(task, start))",
+            (),
+        )?;
+    }
+    if version < 9 {
+        tx.execute("ALTER TABLE tasks ADD COLUMN estimate TEXT", ())?;
+    }
+    if version < 10 {
+        tx.execute("ALTER TABLE tasks ADD COLUMN completed TEXT", ())?;
+        tx.execute(
+            "UPDATE tasks SET completed=modified where status='Completed'",
+            (),
+        )?;
+    }
+    if version < 11 {
+        // full text index over names and descriptions, kept up to date by triggers
+        tx.execute_batch(
+            "CREATE VIRTUAL TABLE tasks_fts USING fts5(short, desc, content='tasks', content_rowid='id', tokenize='unicode61 remove_diacritics 2', prefix='2 3');
+            CREATE TRIGGER tasks_fts_insert AFTER INSERT ON tasks BEGIN
+                INSERT INTO tasks_fts(rowid, short, desc) VALUES (new.id, new.short, new.desc);
+            END;
+            CREATE TRIGGER tasks_fts_delete AFTER DELETE ON tasks BEGIN
+                INSERT INTO tasks_fts(tasks_fts, rowid, short, desc) VALUES ('delete', old.id, old.short, old.desc);
+            END;
+            CREATE TRIGGER tasks_fts_update AFTER UPDATE OF short, desc ON tasks BEGIN
+                INSERT INTO tasks_fts(tasks_fts, rowid, short, desc) VALUES ('delete', old.id, old.short, old.desc);
+                INSERT INTO tasks_fts(rowid, short, desc) VALUES (new.id, new.short, new.desc);
+            END;
+            INSERT INTO tasks_fts(tasks_fts) VALUES ('rebuild');",
+        )?;
+    }
+    if version < 12 {
+        // recurring tasks used to be due when their snooze ended
+        tx.execute("ALTER TABLE tasks ADD COLUMN due TEXT", ())?;
+        tx.execute(
+            "UPDATE tasks SET due=snoozed_until where recurrence IS NOT NULL",
+            (),
+        )?;
+    }
+    if version < 13 {
+        tx.execute(
+            "CREATE TABLE tombstones(uuid TEXT PRIMARY KEY, removed TEXT NOT NULL)",
+            (),
+        )?;
     }
+    if version < 14 {
+        tx.execute(
+            "CREATE TABLE settings(key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+            (),
+        )?;
+    }
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    tx.commit()?;
+    Ok(())
 }
 
 impl Datasource for SqlLiteDataSource {
@@ -44,27 +243,24 @@ impl Datasource for SqlLiteDataSource {
     }
 
     fn write_task(&mut self, task: Task) -> Result<usize> {
-        Ok(self.conn.execute(
-            "INSERT INTO tasks (short,desc,status,created,started,prio) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
-            (
-                task.short,
-                task.desc,
-                task.status,
-                task.created,
-                task.started,
-                task.prio,
-            ),
-        )?)
+        Ok(retry(|| insert(&self.conn, &task))?)
     }
 
     fn remove(&mut self, id: u64) -> Result<usize> {
-        Ok(self
-            .conn
-            .execute("DELETE from tasks where rowid=?1", [id])?)
+        Ok(retry(|| remove(&mut self.conn, id))?)
     }
 
     fn list(&self, tasks: &mut Vec<Task>, filter: &Option<(FilterOptions, String)>) -> Result<()> {
-        let mut select = "SELECT rowid, * from tasks".to_string();
+        self.list_sorted(tasks, filter, &[])
+    }
+
+    fn list_sorted(
+        &self,
+        tasks: &mut Vec<Task>,
+        filter: &Option<(FilterOptions, String)>,
+        keys: &[SortKey],
+    ) -> Result<()> {
+        let mut select = format!("SELECT {} from tasks", TASK_COLUMNS);
         if filter.is_some() {
             select += " WHERE ";
             let f = filter.as_ref().unwrap();
@@ -80,8 +276,9 @@ impl Datasource for SqlLiteDataSource {
             } else {
                 select += &format!("{}='{}'", &opt, &val).to_string();
             }
-            log::debug!("{}", select);
         }
+        select += &sort::order_by(keys);
+        log::debug!("{}", select);
         let mut statement = self.conn.prepare(&select).unwrap();
 
         let tasks_iter = statement.query_map([], row_to_task)?;
@@ -91,26 +288,246 @@ impl Datasource for SqlLiteDataSource {
         Ok(())
     }
 
+    fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        let query = match search::fts_query(query) {
+            Some(query) => query,
+            None => return Ok(vec![]),
+        };
+        let columns: Vec<String> = TASK_COLUMNS
+            .split(',')
+            .map(|c| format!("tasks.{}", c))
+            .collect();
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT {}, hit.name, hit.snippet, hit.rank from tasks JOIN (
+                SELECT rowid AS id, highlight(tasks_fts, 0, ?2, ?3) AS name,
+                    snippet(tasks_fts, 1, ?2, ?3, '…', ?4) AS snippet,
+                    bm25(tasks_fts, 10.0, 1.0) AS rank
+                from tasks_fts where tasks_fts MATCH ?1
+            ) AS hit ON tasks.rowid = hit.id ORDER BY hit.rank",
+            columns.join(",")
+        ))?;
+        let hits = statement
+            .query_map(
+                (
+                    query,
+                    search::MARK_START,
+                    search::MARK_END,
+                    search::SNIPPET_WORDS,
+                ),
+                |row| {
+                    let snippet: String = row.get(18)?;
+                    Ok(Hit {
+                        task: row_to_task(row)?,
+                        name: row.get(17)?,
+                        snippet: snippet.contains(search::MARK_START).then_some(snippet),
+                        rank: row.get(19)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(hits)
+    }
+
     fn get(&self, id: u64) -> Result<Task> {
+        match self.conn.query_row(
+            &format!("SELECT {} from tasks where rowid=?1", TASK_COLUMNS),
+            [id],
+            row_to_task,
+        ) {
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err(Error::not_found(id)),
+            task => Ok(task?),
+        }
+    }
+
+    fn find_by_uuid(&self, prefix: &str) -> Result<Vec<Task>> {
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT {} from tasks where substr(uuid, 1, length(?1))=?1",
+            TASK_COLUMNS
+        ))?;
+        let tasks = statement
+            .query_map([prefix], row_to_task)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tasks)
+    }
+
+    fn save_working_ids(&mut self, uuids: &[Uuid]) -> Result<()> {
+        Ok(retry(|| save_working_ids(&mut self.conn, uuids))?)
+    }
+
+    fn working_ids(&self) -> Result<Vec<Uuid>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT uuid from working_ids ORDER BY pos")?;
+        let uuids = statement
+            .query_map([], |row| uuid_column(row, 0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(uuids)
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
         Ok(self
             .conn
-            .query_row("SELECT rowid,* from tasks where rowid=?1", [id], |row| {
-                row_to_task(row)
-            })?)
+            .query_row("SELECT value from settings where key=?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn save_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        retry(|| {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO settings (key,value) VALUES (?1, ?2)",
+                (key, value),
+            )
+        })?;
+        Ok(())
+    }
+
+    fn settings(&self) -> Result<BTreeMap<String, String>> {
+        let mut statement = self.conn.prepare("SELECT key, value from settings")?;
+        let settings = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(settings)
+    }
+
+    fn add_dependency(&mut self, dep: Dependency) -> Result<usize> {
+        Ok(retry(|| {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO dependencies (task,blocker) VALUES (?1, ?2)",
+                (dep.task.to_string(), dep.blocker.to_string()),
+            )
+        })?)
+    }
+
+    fn remove_dependency(&mut self, dep: Dependency) -> Result<usize> {
+        Ok(retry(|| {
+            self.conn.execute(
+                "DELETE from dependencies where task=?1 AND blocker=?2",
+                (dep.task.to_string(), dep.blocker.to_string()),
+            )
+        })?)
+    }
+
+    fn dependencies(&self) -> Result<Vec<Dependency>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT task, blocker from dependencies")?;
+        let deps = statement
+            .query_map([], |row| {
+                Ok(Dependency {
+                    task: uuid_column(row, 0)?,
+                    blocker: uuid_column(row, 1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(deps)
+    }
+
+    fn add_session(&mut self, session: Session) -> Result<usize> {
+        Ok(retry(|| {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO sessions (task,start,end) VALUES (?1, ?2, ?3)",
+                (session.task.to_string(), session.start, session.end),
+            )
+        })?)
+    }
+
+    fn end_sessions(&mut self, at: Timestamp) -> Result<usize> {
+        Ok(retry(|| {
+            self.conn
+                .execute("UPDATE sessions SET end=?1 where end IS NULL", [at])
+        })?)
+    }
+
+    fn sessions(&self) -> Result<Vec<Session>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT task, start, end from sessions ORDER BY start")?;
+        let sessions = statement
+            .query_map([], |row| {
+                Ok(Session {
+                    task: uuid_column(row, 0)?,
+                    start: row.get(1)?,
+                    end: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(sessions)
+    }
+
+    fn tombstones(&self) -> Result<Vec<Tombstone>> {
+        let mut statement = self.conn.prepare("SELECT uuid, removed from tombstones")?;
+        let tombstones = statement
+            .query_map([], |row| {
+                Ok(Tombstone {
+                    uuid: uuid_column(row, 0)?,
+                    removed: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tombstones)
+    }
+
+    fn save_tombstones(&mut self, tombstones: &[Tombstone]) -> Result<()> {
+        Ok(retry(|| save_tombstones(&mut self.conn, tombstones))?)
     }
 
     fn update_task(&mut self, id: u64, task: Task) -> Result<usize> {
-        Ok(self.conn.execute(
-            "UPDATE tasks SET short=?1,desc=?2,status=?3,created=?4,started=?5 where rowid=?6;",
-            (
-                task.short,
-                task.desc,
-                task.status,
-                task.created,
-                task.started,
-                id,
-            ),
-        )?)
+        Ok(retry(|| update(&self.conn, id, &task))?)
+    }
+
+    fn sync(&mut self, _remote: &str, _policy: MergePolicy) -> Result<SyncReport> {
+        Err(Error::InvalidInput(
+            "the sqllite datasource is not versioned, run with --git to sync".to_string(),
+        ))
+    }
+
+    // the outermost transaction takes the write lock right away: a transaction which has read
+    // before writing fails without waiting if another one wrote in between. Inner ones are savepoints.
+    fn begin_transaction(&mut self) -> Result<()> {
+        match self.depth {
+            0 => retry(|| self.conn.execute_batch("BEGIN IMMEDIATE"))?,
+            _ => self.conn.execute_batch("SAVEPOINT myprio")?,
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        match self.depth {
+            0 => return Err(Error::storage("no transaction to commit")),
+            1 => retry(|| self.conn.execute_batch("COMMIT"))?,
+            _ => self.conn.execute_batch("RELEASE myprio")?,
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn rollback_transaction(&mut self) -> Result<()> {
+        if self.depth == 0 {
+            return Err(Error::storage("no transaction to roll back"));
+        }
+        self.depth -= 1;
+        match self.depth {
+            0 => self.conn.execute_batch("ROLLBACK")?,
+            _ => self
+                .conn
+                .execute_batch("ROLLBACK TO myprio; RELEASE myprio")?,
+        }
+        Ok(())
+    }
+
+    // in WAL mode the changes are kept next to the database file until they are checkpointed
+    fn flush(&mut self) -> Result<()> {
+        let busy: bool = retry(|| {
+            self.conn
+                .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+        })?;
+        if busy {
+            log::warn!("another myprio is using the database, some changes are not in the database file yet");
+        }
+        Ok(())
     }
 }
 
@@ -144,6 +561,106 @@ impl FromSql for TaskStatus {
     }
 }
 
+/// Replaces the working ids with `uuids`, in order starting at 1.
+fn save_working_ids(conn: &mut Connection, uuids: &[Uuid]) -> rusqlite::Result<()> {
+    let tx = conn.savepoint()?;
+    tx.execute("DELETE from working_ids", ())?;
+    for (pos, uuid) in uuids.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO working_ids (pos,uuid) VALUES (?1, ?2)",
+            (pos + 1, uuid.to_string()),
+        )?;
+    }
+    tx.commit()
+}
+
+/// Replaces all tombstones with `tombstones`.
+fn save_tombstones(conn: &mut Connection, tombstones: &[Tombstone]) -> rusqlite::Result<()> {
+    let tx = conn.savepoint()?;
+    tx.execute("DELETE from tombstones", ())?;
+    for tombstone in tombstones {
+        tx.execute(
+            "INSERT OR REPLACE INTO tombstones (uuid,removed) VALUES (?1, ?2)",
+            (tombstone.uuid.to_string(), tombstone.removed),
+        )?;
+    }
+    tx.commit()
+}
+
+/// Removes task `id` with its dependencies and work sessions, and leaves a tombstone.
+fn remove(conn: &mut Connection, id: u64) -> rusqlite::Result<usize> {
+    let tx = conn.savepoint()?;
+    tx.execute(
+        "INSERT OR REPLACE INTO tombstones (uuid,removed) SELECT uuid, ?2 from tasks where rowid=?1",
+        (id, Utc::now()),
+    )?;
+    tx.execute(
+        "DELETE from dependencies where task IN (SELECT uuid from tasks where rowid=?1) OR blocker IN (SELECT uuid from tasks where rowid=?1)",
+        [id],
+    )?;
+    tx.execute(
+        "DELETE from sessions where task IN (SELECT uuid from tasks where rowid=?1)",
+        [id],
+    )?;
+    let removed = tx.execute("DELETE from tasks where rowid=?1", [id])?;
+    tx.commit()?;
+    Ok(removed)
+}
+
+fn insert(conn: &Connection, task: &Task) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT INTO tasks (rowid,short,desc,status,created,started,prio,uuid,modified,parent,rank,snoozed_until,recurrence,series,estimate,completed,due) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17);",
+        params![
+            task.id,
+            &task.short,
+            &task.desc,
+            &task.status,
+            task.created,
+            task.started,
+            &task.prio,
+            task.uuid.to_string(),
+            task.modified,
+            task.parent.map(|p| p.to_string()),
+            task.rank,
+            task.snoozed_until,
+            task.recurrence.as_ref().map(|r| r.to_string()),
+            task.series.map(|s| s.to_string()),
+            task.estimate.as_ref().map(|e| e.to_string()),
+            task.completed,
+            task.due,
+        ],
+    )
+}
+
+fn update(conn: &Connection, id: u64, task: &Task) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE tasks SET short=?1,desc=?2,status=?3,created=?4,started=?5,prio=?6,modified=?7,parent=?8,rank=?9,snoozed_until=?10,recurrence=?11,series=?12,estimate=?13,completed=?14,due=?15 where rowid=?16;",
+        (
+            &task.short,
+            &task.desc,
+            &task.status,
+            task.created,
+            task.started,
+            &task.prio,
+            task.modified,
+            task.parent.map(|p| p.to_string()),
+            task.rank,
+            task.snoozed_until,
+            task.recurrence.as_ref().map(|r| r.to_string()),
+            task.series.map(|s| s.to_string()),
+            task.estimate.as_ref().map(|e| e.to_string()),
+            task.completed,
+            task.due,
+            id,
+        ),
+    )
+}
+
+fn uuid_column(row: &Row<'_>, idx: usize) -> std::result::Result<Uuid, rusqlite::Error> {
+    Uuid::parse_str(&row.get::<_, String>(idx)?)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e)))
+}
+
 fn row_to_task(row: &Row<'_>) -> std::result::Result<Task, rusqlite::Error> {
     Ok(Task {
         id: Some(row.get(0)?),
@@ -153,5 +670,194 @@ fn row_to_task(row: &Row<'_>) -> std::result::Result<Task, rusqlite::Error> {
         created: row.get(4)?,
         started: row.get(5)?,
         prio: row.get(6)?,
+        uuid: uuid_column(row, 7)?,
+        modified: row.get(8)?,
+        parent: match row.get::<_, Option<String>>(9)? {
+            Some(_) => Some(uuid_column(row, 9)?),
+            None => None,
+        },
+        rank: row.get(10)?,
+        snoozed_until: row.get(11)?,
+        recurrence: match row.get::<_, Option<String>>(12)? {
+            Some(rule) => Some(rule.parse().map_err(|e: Error| {
+                rusqlite::Error::FromSqlConversionFailure(12, Type::Text, e.into())
+            })?),
+            None => None,
+        },
+        series: match row.get::<_, Option<String>>(13)? {
+            Some(_) => Some(uuid_column(row, 13)?),
+            None => None,
+        },
+        estimate: match row.get::<_, Option<String>>(14)? {
+            Some(estimate) => Some(estimate.parse().map_err(|e: Error| {
+                rusqlite::Error::FromSqlConversionFailure(14, Type::Text, e.into())
+            })?),
+            None => None,
+        },
+        completed: row.get(15)?,
+        due: row.get(16)?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrade_schema() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let path = f.path().to_string_lossy().to_string();
+        let conn = Connection::open(&path)?;
+        conn.execute("CREATE TABLE tasks(short TEXT, desc TEXT, status TEXT, created TEXT, started TEXT, prio TEXT);", ())?;
+        conn.execute(
+            "INSERT INTO tasks VALUES ('old', '', 'Created', '2024-01-01T00:00:00Z', NULL, 'Watch');",
+            (),
+        )?;
+        drop(conn);
+
+        let ds = SqlLiteDataSource::new(&path)?;
+        let task = ds.get(1)?;
+        assert_eq!(task.short, "old");
+        assert_eq!(task.modified, task.created);
+        assert_eq!(ds.search("old")?.len(), 1);
+        // opening again must not run the migration twice
+        drop(ds);
+        assert_eq!(SqlLiteDataSource::new(&path)?.get(1)?.uuid, task.uuid);
+        Ok(())
+    }
+
+    #[test]
+    fn ids_are_not_reused() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut ds = SqlLiteDataSource::new(&f.path().to_string_lossy().to_string())?;
+        for name in ["one", "two"] {
+            ds.write_task(Task::new(
+                name.to_string(),
+                "".to_string(),
+                TaskPriority::ToDo,
+            ))?;
+        }
+        ds.remove(2)?;
+        ds.conn.execute("VACUUM", ())?;
+        ds.write_task(Task::new(
+            "three".to_string(),
+            "".to_string(),
+            TaskPriority::ToDo,
+        ))?;
+        assert!(ds.get(2).is_err());
+        assert_eq!(ds.get(3)?.short, "three");
+        Ok(())
+    }
+
+    #[test]
+    fn full_text_search() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut ds = SqlLiteDataSource::new(&f.path().to_string_lossy().to_string())?;
+        for (name, desc) in [
+            ("Write release notes", ""),
+            ("Deployment", "check the release, then write the notes"),
+            ("Groceries", ""),
+        ] {
+            ds.write_task(Task::new(
+                name.to_string(),
+                desc.to_string(),
+                TaskPriority::ToDo,
+            ))?;
+        }
+
+        let hits = ds.search("release notes")?;
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].task.short, "Write release notes");
+        assert_eq!(
+            hits[0].name,
+            format!(
+                "Write {}release{} {}notes{}",
+                search::MARK_START,
+                search::MARK_END,
+                search::MARK_START,
+                search::MARK_END
+            )
+        );
+        assert!(hits[0].snippet.is_none());
+        assert!(hits[1].snippet.is_some());
+        assert_eq!(ds.search("\"release notes\"")?.len(), 1);
+        assert_eq!(ds.search("deploy*")?.len(), 1);
+
+        let mut groceries = ds.get(3)?;
+        groceries.desc = "milk and release candidate".to_string();
+        ds.update_task(3, groceries)?;
+        ds.remove(1)?;
+        assert_eq!(ds.search("release")?.len(), 2);
+        assert_eq!(ds.search("\"unbalanced")?.len(), 0);
+        assert_eq!(ds.search("release\"")?.len(), 2);
+        assert_eq!(ds.search("NOT OR")?.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_writers() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let path = f.path().to_string_lossy().to_string();
+        SqlLiteDataSource::new(&path)?;
+
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let path = path.clone();
+                thread::spawn(move || -> Result<()> {
+                    let mut ds = SqlLiteDataSource::new(&path)?;
+                    for i in 0..10 {
+                        // a read first, so the transaction has to wait for the write lock
+                        ds.begin_transaction()?;
+                        ds.list(&mut vec![], &None)?;
+                        for name in ["a", "b"] {
+                            ds.write_task(Task::new(
+                                format!("{} {} {}", w, i, name),
+                                "".to_string(),
+                                TaskPriority::ToDo,
+                            ))?;
+                        }
+                        ds.commit_transaction()?;
+                        let tasks = ds.find_by_uuid("")?;
+                        let own = |name| {
+                            let short = format!("{} {} {}", w, i, name);
+                            tasks.iter().find(|t| t.short == short).unwrap()
+                        };
+                        let (a, b) = (own("a"), own("b"));
+                        ds.add_dependency(Dependency {
+                            task: a.uuid,
+                            blocker: b.uuid,
+                        })?;
+                        ds.add_session(Session {
+                            task: a.uuid,
+                            start: Utc::now(),
+                            end: None,
+                        })?;
+                        ds.end_sessions(Utc::now())?;
+                        ds.remove_dependency(Dependency {
+                            task: a.uuid,
+                            blocker: b.uuid,
+                        })?;
+                        ds.remove(b.id.unwrap())?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap()?;
+        }
+
+        let ds = SqlLiteDataSource::new(&path)?;
+        let mut tasks = vec![];
+        ds.list(&mut tasks, &None)?;
+        assert_eq!(tasks.len(), 4 * 10);
+        assert_eq!(ds.sessions()?.len(), 4 * 10);
+        assert!(ds.sessions()?.iter().all(|s| s.end.is_some()));
+        assert!(ds.dependencies()?.is_empty());
+        let mode: String = ds
+            .conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+        assert_eq!(mode, "wal");
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use crate::task::stats::Stats;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// one per TaskStatus in the order of the enum, deleted tasks are left out
+const SHADES: [char; 5] = ['░', '▒', '█', '▓', '-'];
+const LABEL: usize = 14;
+
+/// One character per value, scaled between zero and the largest value.
+pub fn sparkline(values: &[usize]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|v| match max {
+            0 => SPARKS[0],
+            _ => SPARKS[v * (SPARKS.len() - 1) / max],
+        })
+        .collect()
+}
+
+/// `value` out of `max` as a number of characters in a bar `width` wide.
+fn scale(value: usize, max: usize, width: usize) -> usize {
+    match max {
+        0 => 0,
+        _ => (value * width + max / 2) / max,
+    }
+}
+
+/// Trends from `stats` as sparklines, a bar chart of completions and a cumulative flow
+/// diagram, fitted into `width` columns.
+pub fn render(stats: &Stats, width: usize) -> String {
+    let mut out = String::new();
+    // no bars at all on a terminal too narrow for them, the numbers are still shown
+    let bar_width = width.saturating_sub(LABEL + 6);
+    // keep the newest weeks if there are more than fit on a line
+    let skip = stats.weeks.len().saturating_sub(bar_width);
+
+    let open: Vec<usize> = stats.flow.iter().skip(skip).map(|f| f.open).collect();
+    let done: Vec<usize> = stats.weeks.iter().skip(skip).map(|w| w.completed).collect();
+    out += &format!(
+        "{:<LABEL$}{} {}\n",
+        "Open tasks",
+        sparkline(&open),
+        stats.flow.last().map_or(0, |f| f.open)
+    );
+    out += &format!(
+        "{:<LABEL$}{} {}\n",
+        "Completed",
+        sparkline(&done),
+        stats.weeks.last().map_or(0, |w| w.completed)
+    );
+
+    out += "\nCompleted per week\n";
+    let max = stats.weeks.iter().map(|w| w.completed).max().unwrap_or(0);
+    for w in &stats.weeks {
+        out += &format!(
+            "{:<LABEL$}{} {}\n",
+            w.week,
+            "█".repeat(scale(w.completed, max, bar_width)),
+            w.completed
+        );
+    }
+
+    out += "\nCumulative flow\n";
+    if let Some(first) = stats.flow.first() {
+        // the legend wraps rather than running off a narrow terminal
+        let mut line = String::new();
+        for (c, shade) in first.statuses.iter().zip(SHADES) {
+            let entry = format!("{}{}", shade, c.name);
+            if !line.is_empty() && line.chars().count() + 1 + entry.chars().count() > width {
+                out += &format!("{}\n", line);
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &entry;
+        }
+        out += &format!("{}\n", line);
+    }
+    let max = stats
+        .flow
+        .iter()
+        .map(|f| f.statuses.iter().take(SHADES.len()).map(|c| c.count).sum())
+        .max()
+        .unwrap_or(0);
+    for f in &stats.flow {
+        let mut line = String::new();
+        let mut total = 0;
+        // closed work at the bottom, like the layers of a flow diagram
+        for (c, shade) in f.statuses.iter().take(SHADES.len()).zip(SHADES).rev() {
+            let from = scale(total, max, bar_width);
+            total += c.count;
+            let to = scale(total, max, bar_width);
+            line.extend(std::iter::repeat_n(shade, to - from));
+        }
+        out += &format!("{:<LABEL$}{} {}\n", f.week, line, total);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::stats;
+    use crate::task::{Task, TaskPriority, TaskStatus};
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn charts() {
+        assert_eq!(sparkline(&[0, 1, 2, 7]), "▁▂▃█");
+        assert_eq!(sparkline(&[0, 0]), "▁▁");
+        assert_eq!(scale(3, 6, 10), 5);
+        assert_eq!(scale(1, 0, 10), 0);
+    }
+
+    #[test]
+    fn narrow_terminals() {
+        let now = Utc::now();
+        let tasks: Vec<Task> = (0..30)
+            .map(|i| {
+                let mut t = Task::new(format!("{}", i), "".to_string(), TaskPriority::ToDo);
+                t.created = now - Duration::weeks(i % 8);
+                if i % 3 == 0 {
+                    t.status = TaskStatus::Completed;
+                    t.completed = Some(now - Duration::days(i % 40));
+                }
+                t
+            })
+            .collect();
+        let stats = stats::stats(&tasks, 8, now);
+        // lines with bars or the legend, week labels contain the `-` shade as well
+        let bars = |chart: &str| -> Vec<String> {
+            chart
+                .lines()
+                .filter(|l| l.chars().any(|c| SPARKS.contains(&c) || "░▒▓".contains(c)))
+                .map(|l| l.to_string())
+                .collect()
+        };
+        for width in [12, 24, 30, 80] {
+            let chart = render(&stats, width);
+            for line in bars(&chart) {
+                assert!(line.chars().count() <= width, "{:?} at {}", line, width);
+            }
+        }
+        // too narrow for any bar, only the legend is left
+        let chart = render(&stats, 12);
+        assert_eq!(
+            bars(&chart),
+            vec!["░Created", "▒Started", "█Completed", "▓WontDo"]
+        );
+        assert!(chart.starts_with(&format!(
+            "{:<LABEL$} {}\n",
+            "Open tasks",
+            stats.flow.last().unwrap().open
+        )));
+        // the busiest week takes the full width
+        assert!(render(&stats, 80).contains(&"█".repeat(80 - LABEL - 6)));
+    }
+}
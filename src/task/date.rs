@@ -0,0 +1,82 @@
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+
+use crate::error::{Error, Result};
+use crate::task::Timestamp;
+
+/// A span of time like `3d`, `2w` or `12h`.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let invalid = || Error::InvalidInput(format!("not a duration: {}, use e.g. 3d, 2w or 12h", s));
+    let unit = s.chars().last().ok_or_else(invalid)?;
+    let n: i64 = s[..s.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    match unit {
+        'h' => Duration::try_hours(n),
+        'd' => Duration::try_days(n),
+        'w' => Duration::try_weeks(n),
+        _ => return Err(invalid()),
+    }
+    .ok_or_else(|| Error::InvalidInput(format!("{} is too long", s)))
+}
+
+/// A point in time given as a date (`2026-11-02`, the start of that day in local time)
+/// or relative to `now` (`+3d`, `-2w`).
+pub fn parse_date(s: &str, now: Timestamp) -> Result<Timestamp> {
+    let s = s.trim();
+    let out_of_range = || Error::InvalidInput(format!("{} is too far away", s));
+    if let Some(span) = s.strip_prefix('+') {
+        return now
+            .checked_add_signed(parse_duration(span)?)
+            .ok_or_else(out_of_range);
+    }
+    if let Some(span) = s.strip_prefix('-') {
+        return now
+            .checked_sub_signed(parse_duration(span)?)
+            .ok_or_else(out_of_range);
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| {
+        Error::InvalidInput(format!("not a date: {}, use e.g. 2026-11-02 or +3d", s))
+    })?;
+    start_of_day(date)
+}
+
+/// Midnight at the start of `date` in local time.
+pub fn start_of_day(date: NaiveDate) -> Result<Timestamp> {
+    match Local.from_local_datetime(&date.and_time(Default::default())) {
+        chrono::LocalResult::Single(t) | chrono::LocalResult::Ambiguous(t, _) => Ok(t.into()),
+        chrono::LocalResult::None => Err(Error::InvalidInput(format!(
+            "{} has no midnight in local time",
+            date
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn dates() {
+        let now = Utc::now();
+        assert_eq!(parse_date("+3d", now).unwrap(), now + Duration::days(3));
+        assert_eq!(parse_date("+2w", now).unwrap(), now + Duration::days(14));
+        assert_eq!(parse_date("-12h", now).unwrap(), now - Duration::hours(12));
+        assert!(parse_date("+3x", now).is_err());
+        assert!(parse_date("next week", now).is_err());
+        assert!(matches!(
+            parse_date("+9999999999999d", now),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            parse_date("+100000000d", now),
+            Err(Error::InvalidInput(_))
+        ));
+        let date = parse_date("2030-01-31", now).unwrap();
+        assert_eq!(
+            date.with_timezone(&Local).date_naive(),
+            NaiveDate::from_ymd_opt(2030, 1, 31).unwrap()
+        );
+    }
+}
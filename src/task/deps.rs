@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::task::{Task, TaskPriority};
+
+/// `task` can't be worked on before `blocker` is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Dependency {
+    pub task: Uuid,
+    pub blocker: Uuid,
+}
+
+/// Whether adding `dep` would make a task wait for itself, directly or through other tasks.
+pub fn creates_cycle(deps: &[Dependency], dep: &Dependency) -> bool {
+    let mut seen = HashSet::new();
+    let mut stack = vec![dep.blocker];
+    while let Some(uuid) = stack.pop() {
+        if uuid == dep.task {
+            return true;
+        }
+        if !seen.insert(uuid) {
+            continue;
+        }
+        stack.extend(deps.iter().filter(|d| d.task == uuid).map(|d| d.blocker));
+    }
+    false
+}
+
+/// Tasks waiting for at least one blocker which is still open. `all` has to contain
+/// every task, not only the ones being displayed.
+pub fn blocked(all: &[Task], deps: &[Dependency]) -> HashSet<Uuid> {
+    let open: HashSet<Uuid> = all
+        .iter()
+        .filter(|t| t.status.is_open())
+        .map(|t| t.uuid)
+        .collect();
+    deps.iter()
+        .filter(|d| open.contains(&d.blocker) && open.contains(&d.task))
+        .map(|d| d.task)
+        .collect()
+}
+
+/// The most urgent priority of `task` and of all open tasks waiting for it, directly or not.
+pub fn effective_priority(task: &Task, all: &[Task], deps: &[Dependency]) -> TaskPriority {
+    let mut prio = task.prio.clone();
+    let mut seen = HashSet::new();
+    let mut stack = vec![task.uuid];
+    while let Some(uuid) = stack.pop() {
+        if !seen.insert(uuid) {
+            continue;
+        }
+        for d in deps.iter().filter(|d| d.blocker == uuid) {
+            if let Some(waiting) = all.iter().find(|t| t.uuid == d.task && t.status.is_open()) {
+                prio = prio.min(waiting.prio.clone());
+                stack.push(waiting.uuid);
+            }
+        }
+    }
+    prio
+}
+
+/// Orders tasks by priority, but never ahead of the tasks blocking them: blockers inherit
+/// the priority of the tasks waiting for them. Ties keep the order of `tasks`.
+pub fn next_order(tasks: Vec<Task>, all: &[Task], deps: &[Dependency]) -> Vec<Task> {
+    let mut tasks: Vec<(TaskPriority, Task)> = tasks
+        .into_iter()
+        .map(|t| (effective_priority(&t, all, deps), t))
+        .collect();
+    tasks.sort_by(|(a, _), (b, _)| a.cmp(b));
+    blockers_first(tasks.into_iter().map(|(_, t)| t).collect(), deps)
+}
+
+/// Keeps the order of `tasks` as far as possible, but never puts a task ahead of the
+/// tasks blocking it.
+pub fn blockers_first(tasks: Vec<Task>, deps: &[Dependency]) -> Vec<Task> {
+    let mut pending: Vec<usize> = (0..tasks.len()).collect();
+    let mut order: Vec<usize> = vec![];
+    while !pending.is_empty() {
+        // the first task whose blockers in the list are all placed already, or just the
+        // first one if the dependencies loop
+        let pos = pending
+            .iter()
+            .position(|&i| {
+                deps.iter().filter(|d| d.task == tasks[i].uuid).all(|d| {
+                    !pending
+                        .iter()
+                        .any(|&j| j != i && tasks[j].uuid == d.blocker)
+                })
+            })
+            .unwrap_or(0);
+        order.push(pending.remove(pos));
+    }
+    let mut tasks: Vec<Option<Task>> = tasks.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| tasks[i].take()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(name: &str, prio: TaskPriority) -> Task {
+        Task::new(name.to_string(), "".to_string(), prio)
+    }
+
+    #[test]
+    fn cycles_and_order() {
+        let report = task("report", TaskPriority::UrgentAndImportant);
+        let data = task("data", TaskPriority::SomeDay);
+        let other = task("other", TaskPriority::ImportantNotUrgent);
+        let deps = vec![Dependency {
+            task: report.uuid,
+            blocker: data.uuid,
+        }];
+        assert!(creates_cycle(
+            &deps,
+            &Dependency {
+                task: data.uuid,
+                blocker: report.uuid
+            }
+        ));
+        assert!(!creates_cycle(
+            &deps,
+            &Dependency {
+                task: other.uuid,
+                blocker: report.uuid
+            }
+        ));
+
+        let all = vec![other.clone(), report.clone(), data.clone()];
+        assert_eq!(
+            blocked(&all, &deps).into_iter().collect::<Vec<_>>(),
+            vec![report.uuid]
+        );
+        assert_eq!(
+            effective_priority(&data, &all, &deps),
+            TaskPriority::UrgentAndImportant
+        );
+        let names: Vec<String> = next_order(all.clone(), &all, &deps)
+            .into_iter()
+            .map(|t| t.short)
+            .collect();
+        assert_eq!(names, vec!["data", "report", "other"]);
+    }
+}
@@ -0,0 +1,313 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::task::time::{self, Session};
+use crate::task::{Task, TaskStatus, Timestamp};
+
+const MINUTES_PER_DAY: i64 = 8 * 60;
+const MINUTES_PER_WEEK: i64 = 5 * MINUTES_PER_DAY;
+
+/// Expected effort for a task, either as working time (`90m`, `2h`, `1.5d` with 8 hour
+/// days and 5 day weeks) or as story points (`3pt`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Estimate {
+    Minutes(i64),
+    Points(u32),
+}
+
+/// Estimates added up, time and points separately.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Total {
+    pub minutes: i64,
+    pub points: u32,
+}
+
+impl Total {
+    pub fn add(&mut self, estimate: &Estimate) {
+        match estimate {
+            Estimate::Minutes(m) => self.minutes = self.minutes.saturating_add(*m),
+            Estimate::Points(p) => self.points = self.points.saturating_add(*p),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minutes == 0 && self.points == 0
+    }
+}
+
+/// A completed, estimated task and the time actually tracked on it.
+#[derive(Debug)]
+pub struct Actual {
+    pub task: Task,
+    pub estimate: Estimate,
+    pub tracked: Duration,
+}
+
+/// Estimates compared to tracked time for all completed tasks which have both.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub tasks: Vec<Actual>,
+}
+
+pub fn report(all: &[Task], sessions: &[Session], now: Timestamp) -> Report {
+    let tasks = all
+        .iter()
+        .filter(|t| t.status == TaskStatus::Completed)
+        .filter_map(|t| {
+            let estimate = t.estimate?;
+            let tracked = time::spent(t, sessions, now);
+            if tracked.is_zero() {
+                return None;
+            }
+            Some(Actual {
+                task: t.clone(),
+                estimate,
+                tracked,
+            })
+        })
+        .collect();
+    Report { tasks }
+}
+
+impl Report {
+    /// Tracked time divided by estimated time, over all tasks estimated in time.
+    /// Above 1 means the estimates were too optimistic.
+    pub fn time_ratio(&self) -> Option<f64> {
+        let (estimated, tracked) = self
+            .tasks
+            .iter()
+            .filter_map(|a| match a.estimate {
+                Estimate::Minutes(m) => Some((m, a.tracked.num_minutes())),
+                Estimate::Points(_) => None,
+            })
+            .fold((0i64, 0i64), |(e, t), (m, a)| {
+                (e.saturating_add(m), t.saturating_add(a))
+            });
+        match estimated {
+            0 => None,
+            _ => Some(tracked as f64 / estimated as f64),
+        }
+    }
+
+    /// Average tracked time per story point.
+    pub fn per_point(&self) -> Option<Duration> {
+        let (points, tracked) = self
+            .tasks
+            .iter()
+            .filter_map(|a| match a.estimate {
+                Estimate::Points(p) => Some((p as i64, a.tracked)),
+                Estimate::Minutes(_) => None,
+            })
+            .fold((0, Duration::zero()), |(p, t), (n, a)| (p + n, t + a));
+        match points {
+            0 => None,
+            // in i64, a sum of points can be more than an i32 holds
+            _ => Duration::try_seconds(tracked.num_seconds() / points),
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.tasks.is_empty() {
+            return writeln!(
+                f,
+                "No completed tasks with an estimate and tracked time yet."
+            );
+        }
+        writeln!(f, "  estimate   tracked  task")?;
+        for a in &self.tasks {
+            writeln!(
+                f,
+                "  {:>8}  {:>8}  #{} {}",
+                a.estimate.to_string(),
+                time::format_duration(a.tracked),
+                a.task.id.unwrap_or_default(),
+                a.task.short
+            )?;
+        }
+        if let Some(ratio) = self.time_ratio() {
+            writeln!(
+                f,
+                "Time estimates: took {:.0}% of the estimated time",
+                ratio * 100.0
+            )?;
+        }
+        if let Some(per_point) = self.per_point() {
+            writeln!(
+                f,
+                "Story points: {} per point",
+                time::format_duration(per_point)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Points(p) => write!(f, "{}pt", p),
+            Self::Minutes(m) if *m > 0 && m % MINUTES_PER_DAY == 0 => {
+                write!(f, "{}d", m / MINUTES_PER_DAY)
+            }
+            Self::Minutes(m) if *m > 0 && m % 60 == 0 => write!(f, "{}h", m / 60),
+            Self::Minutes(m) => write!(f, "{}m", m),
+        }
+    }
+}
+
+impl Display for Total {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if self.minutes > 0 {
+            parts.push(match Duration::try_minutes(self.minutes) {
+                Some(d) => time::format_duration(d),
+                None => format!("{}m", self.minutes),
+            });
+        }
+        if self.points > 0 {
+            parts.push(format!("{}pt", self.points));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl FromStr for Estimate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            Error::InvalidInput(format!(
+                "invalid estimate {}, use e.g. 90m, 2h, 1.5d or 3pt",
+                s
+            ))
+        };
+        let too_large = || Error::InvalidInput(format!("estimate {} is too large", s));
+        let s = s.trim().to_lowercase();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(invalid)?;
+        let (n, unit) = s.split_at(split);
+        let n: f64 = n.parse().map_err(|_| invalid())?;
+        if n <= 0.0 {
+            return Err(invalid());
+        }
+        let minutes = match unit.trim() {
+            "m" | "min" => 1,
+            "h" => 60,
+            "d" => MINUTES_PER_DAY,
+            "w" => MINUTES_PER_WEEK,
+            "pt" | "p" | "sp" | "points" if n.fract() == 0.0 => {
+                if n > u32::MAX as f64 {
+                    return Err(too_large());
+                }
+                return Ok(Self::Points(n as u32));
+            }
+            _ => return Err(invalid()),
+        };
+        // anything which can't be shown as a duration is too large, `as` would saturate
+        let minutes = (n * minutes as f64).round();
+        if minutes >= i64::MAX as f64 || Duration::try_minutes(minutes as i64).is_none() {
+            return Err(too_large());
+        }
+        Ok(Self::Minutes(minutes as i64))
+    }
+}
+
+impl TryFrom<String> for Estimate {
+    type Error = Error;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Estimate> for String {
+    fn from(e: Estimate) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+    use chrono::Utc;
+
+    #[test]
+    fn estimates() {
+        for (given, shown) in [
+            ("90m", "90m"),
+            ("2h", "2h"),
+            ("1.5d", "12h"),
+            ("1w", "5d"),
+            ("3sp", "3pt"),
+        ] {
+            assert_eq!(given.parse::<Estimate>().unwrap().to_string(), shown);
+        }
+        assert!("2".parse::<Estimate>().is_err());
+        assert!("1.5pt".parse::<Estimate>().is_err());
+        assert!("0h".parse::<Estimate>().is_err());
+        for too_large in ["999999999999w", "99999999999999999999999m", "5000000000pt"] {
+            assert!(matches!(
+                too_large.parse::<Estimate>(),
+                Err(Error::InvalidInput(_))
+            ));
+        }
+        let largest = Estimate::Minutes(Duration::max_value().num_minutes());
+        assert_eq!(largest.to_string().parse::<Estimate>().unwrap(), largest);
+        let mut total = Total::default();
+        total.add(&largest);
+        total.add(&largest);
+        total.add(&Estimate::Points(u32::MAX));
+        total.add(&Estimate::Points(1));
+        assert_eq!(total.points, u32::MAX);
+        // beyond what a duration can hold
+        assert_eq!(
+            total.to_string(),
+            format!(
+                "{}m, {}pt",
+                2 * Duration::max_value().num_minutes(),
+                u32::MAX
+            )
+        );
+
+        let now = Utc::now();
+        let mut fix = Task::new("fix".to_string(), "".to_string(), TaskPriority::ToDo);
+        fix.estimate = Some(Estimate::Minutes(60));
+        fix.status = TaskStatus::Completed;
+        let mut open = fix.clone();
+        open.uuid = uuid::Uuid::new_v4();
+        open.status = TaskStatus::Started;
+        let sessions: Vec<Session> = [&fix, &open]
+            .iter()
+            .map(|t| Session {
+                task: t.uuid,
+                start: now - Duration::minutes(90),
+                end: Some(now),
+            })
+            .collect();
+        let report = report(&[fix, open], &sessions, now);
+        assert_eq!(report.tasks.len(), 1);
+        assert_eq!(report.time_ratio(), Some(1.5));
+        assert_eq!(report.per_point(), None);
+
+        // 2^32 points in total, too many to divide by as an i32
+        let huge: Vec<Actual> = (0..2)
+            .map(|_| Actual {
+                task: Task::new("huge".to_string(), "".to_string(), TaskPriority::ToDo),
+                estimate: Estimate::Points(1 << 31),
+                tracked: Duration::days(1000),
+            })
+            .collect();
+        let per_point = Report { tasks: huge }.per_point().unwrap();
+        assert_eq!(per_point, Duration::seconds(2 * 1000 * 86400 / (1 << 32)));
+        assert!(per_point >= Duration::zero());
+    }
+}
@@ -0,0 +1,133 @@
+use std::str::FromStr;
+
+use strum::IntoEnumIterator;
+
+use crate::error::Error;
+use crate::task::{time, Task, TaskPriority, TaskStatus};
+
+/// Selects tasks for bulk changes, e.g. `prio:Watch status:Created` or `group:release-1`.
+/// Terms on the same field are alternatives, terms on different fields must all match.
+/// A word without a field matches part of the name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    prios: Vec<TaskPriority>,
+    statuses: Vec<TaskStatus>,
+    /// names of top level tasks, a group being one with all its subtasks
+    groups: Vec<String>,
+    names: Vec<String>,
+}
+
+/// Lowercase letters and digits only, so `some-day`, `SomeDay` and `Some Day` are the same.
+fn simplify(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// A priority given loosely, like `SomeDay`, `some-day` or `Some Day`.
+pub fn parse_priority(s: &str) -> std::result::Result<TaskPriority, Error> {
+    TaskPriority::iter()
+        .find(|p| simplify(&p.to_string()) == simplify(s))
+        .ok_or_else(|| Error::InvalidPriority(s.to_string()))
+}
+
+/// A status given loosely, like `WontDo` or `wont-do`.
+pub fn parse_status(s: &str) -> std::result::Result<TaskStatus, Error> {
+    TaskStatus::iter()
+        .find(|st| simplify(&st.to_string()) == simplify(s))
+        .ok_or_else(|| Error::InvalidStatus(s.to_string()))
+}
+
+impl Filter {
+    pub fn matches(&self, task: &Task, all: &[Task]) -> bool {
+        let name = task.short.to_lowercase();
+        (self.prios.is_empty() || self.prios.contains(&task.prio))
+            && (self.statuses.is_empty() || self.statuses.contains(&task.status))
+            && (self.groups.is_empty()
+                || self
+                    .groups
+                    .iter()
+                    .any(|g| time::root(all, task).short.eq_ignore_ascii_case(g)))
+            && self.names.iter().all(|n| name.contains(n))
+    }
+
+    /// The tasks of `all` matching the filter.
+    pub fn select(&self, all: &[Task]) -> Vec<Task> {
+        all.iter()
+            .filter(|t| self.matches(t, all))
+            .cloned()
+            .collect()
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut filter = Filter::default();
+        for term in s.split_whitespace() {
+            match term.split_once(':') {
+                Some(("prio" | "priority", value)) => filter.prios.push(parse_priority(value)?),
+                Some(("status", value)) => filter.statuses.push(parse_status(value)?),
+                Some(("group", value)) => filter.groups.push(value.to_string()),
+                Some(("name", value)) => filter.names.push(value.to_lowercase()),
+                Some((field, _)) => {
+                    return Err(Error::InvalidInput(format!(
+                        "unknown filter field {}, use prio, status, group or name",
+                        field
+                    )))
+                }
+                None => filter.names.push(term.to_lowercase()),
+            }
+        }
+        if filter == Filter::default() {
+            return Err(Error::InvalidInput(
+                "the filter is empty, give e.g. prio:Watch".to_string(),
+            ));
+        }
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let release = Task::new("Release-1".to_string(), "".to_string(), TaskPriority::ToDo);
+        let mut notes = Task::new("notes".to_string(), "".to_string(), TaskPriority::Watch);
+        notes.parent = Some(release.uuid);
+        let mut old = Task::new("old idea".to_string(), "".to_string(), TaskPriority::Watch);
+        old.status = TaskStatus::Started;
+        let all = vec![release, notes, old];
+
+        let names = |f: &str| -> Vec<String> {
+            f.parse::<Filter>()
+                .unwrap()
+                .select(&all)
+                .into_iter()
+                .map(|t| t.short)
+                .collect()
+        };
+        assert_eq!(names("group:release-1"), vec!["Release-1", "notes"]);
+        assert_eq!(names("prio:Watch status:Created"), vec!["notes"]);
+        assert_eq!(names("prio:watch status:created status:started").len(), 2);
+        assert_eq!(names("prio:to-do idea"), Vec::<String>::new());
+        assert_eq!(names("name:IDEA"), vec!["old idea"]);
+        assert!(matches!("".parse::<Filter>(), Err(Error::InvalidInput(_))));
+        assert!(matches!(
+            "prio:later".parse::<Filter>(),
+            Err(Error::InvalidPriority(_))
+        ));
+        assert!(matches!(
+            "status:gone".parse::<Filter>(),
+            Err(Error::InvalidStatus(_))
+        ));
+        assert!(matches!(
+            "due:today".parse::<Filter>(),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
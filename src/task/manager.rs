@@ -1,18 +1,73 @@
+use chrono::{Duration, Utc};
 use log;
+use strum::IntoEnumIterator;
 
 use crate::app::FilterOptions;
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::task::merge::{self, Conflict, MergePolicy, SyncReport};
 use crate::{
     app::Datasources,
-    source::{sqllite::SqlLiteDataSource, Datasource},
-    task::Task,
+    source::{self, git::GitDataSource, Datasource},
+    task::{
+        deps::{self, Dependency},
+        estimate,
+        filter::Filter,
+        next::{self, Suggestion},
+        recur::{self, Recurrence},
+        review::{self, Action, Stale, Threshold},
+        search::Hit,
+        sort::{self, SortKey},
+        stats::{self, Stats},
+        time::{self, Session, Timesheet},
+        Task, TaskPriority, TaskStatus, Timestamp,
+    },
 };
+use uuid::Uuid;
 
 const TASK_SHORT_LEN: usize = 30;
 const TASK_DESC_LEN: usize = 40;
+/// Shortest uuid prefix accepted as a task reference, like git short hashes.
+const MIN_UUID_PREFIX_LEN: usize = 4;
+/// Setting holding the saved review thresholds, e.g. `Watch=7,Some Day=120`.
+const REVIEW_THRESHOLDS: &str = "review.thresholds";
 
 pub struct TaskManager {
     source: Box<dyn Datasource>,
+    working_ids: bool,
+}
+
+/// Sets up a `TaskManager` with more options than `TaskManager::new`, e.g.
+/// `TaskManager::builder(Datasources::Json, "tasks.json").git(true).build()?`.
+pub struct TaskManagerBuilder {
+    datasource: Datasources,
+    path: String,
+    git: bool,
+    working_ids: bool,
+}
+
+impl TaskManagerBuilder {
+    /// Commit every change to a git repository next to the datasource file.
+    pub fn git(mut self, enabled: bool) -> Self {
+        self.git = enabled;
+        self
+    }
+
+    /// See `TaskManager::use_working_ids`.
+    pub fn working_ids(mut self, enabled: bool) -> Self {
+        self.working_ids = enabled;
+        self
+    }
+
+    /// Opens the datasource, failing if it can't be read or created.
+    pub fn build(self) -> Result<TaskManager> {
+        let mut ds = source::open(self.datasource, &self.path)?;
+        if self.git {
+            ds = Box::new(GitDataSource::new(ds, self.datasource, &self.path)?);
+        }
+        let mut mgr = TaskManager::with_source(ds)?;
+        mgr.use_working_ids(self.working_ids);
+        Ok(mgr)
+    }
 }
 
 impl TaskManager {
@@ -21,77 +76,864 @@ impl TaskManager {
     }
 
     // TODO: path should probably be a PathBuf or Path
-    pub fn new(datasource: Datasources, path: &String) -> Self {
-        let ds: Box<dyn Datasource> = match datasource {
-            Datasources::SqlLite => Box::new(SqlLiteDataSource::new(path).unwrap_or_else(|e| {
-                panic!("creating requested sqllite datasource failed: {e:?}");
-            })),
-        };
-        match ds.start() {
-            Ok(()) => log::info!("datasource ready"),
-            Err(e) => {
-                log::error!("datasource failed to initialize: {e:?}");
-                panic!("can't proceed without datasource");
-            }
+    /// Opens the datasource at `path`, failing if it can't be read or created.
+    pub fn new(datasource: Datasources, path: &str) -> Result<Self> {
+        Self::builder(datasource, path).build()
+    }
+
+    pub fn builder(datasource: Datasources, path: &str) -> TaskManagerBuilder {
+        TaskManagerBuilder {
+            datasource,
+            path: path.to_string(),
+            git: false,
+            working_ids: false,
         }
-        Self { source: ds }
     }
 
-    pub fn list(&mut self, filter: &Option<(FilterOptions, String)>) {
+    fn with_source(ds: Box<dyn Datasource>) -> Result<Self> {
+        ds.start()?;
+        log::info!("datasource ready");
+        Ok(Self {
+            source: ds,
+            working_ids: false,
+        })
+    }
+
+    /// Runs `f` in one datasource transaction, so its changes are kept all together or,
+    /// if it fails, not at all.
+    fn transaction<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        source::transaction_of(self, |mgr| mgr.source.as_mut(), f)
+    }
+
+    /// Number open tasks 1..N in `list`, and resolve plain numbers given by the user to
+    /// the task shown under that number in the last list. Stable ids are then given as `#<id>`.
+    pub fn use_working_ids(&mut self, enabled: bool) {
+        self.working_ids = enabled;
+    }
+
+    /// Prints the tasks sorted by `sort`, or by priority and manual rank if it is empty.
+    pub fn list(
+        &mut self,
+        filter: &Option<(FilterOptions, String)>,
+        sort: &[SortKey],
+    ) -> Result<()> {
+        let rows = self.list_rows(filter, sort, false)?;
+        self.print_task_list(&rows);
+        Ok(())
+    }
+
+    /// Like `list`, but only open tasks which are not blocked by other tasks, in the order
+    /// they can be worked on. Blockers still come before the tasks they block if `sort`
+    /// is given.
+    pub fn list_actionable(
+        &mut self,
+        filter: &Option<(FilterOptions, String)>,
+        sort: &[SortKey],
+    ) -> Result<()> {
+        let rows = self.list_rows(filter, sort, true)?;
+        self.print_task_list(&rows);
+        Ok(())
+    }
+
+    /// The filtered tasks in display order, subtasks following their parent.
+    fn list_rows(
+        &mut self,
+        filter: &Option<(FilterOptions, String)>,
+        sort: &[SortKey],
+        actionable: bool,
+    ) -> Result<Vec<ListRow>> {
+        let keys = match sort.is_empty() {
+            true => &sort::DEFAULT_ORDER[..],
+            false => sort,
+        };
         let mut tasks: Vec<Task> = Vec::new();
-        match self.source.list(&mut tasks, filter) {
-            Ok(tsks) => tsks,
-            Err(e) => {
-                log::error!("failed to fetch task list from datasource: {e:?}");
-                return;
+        self.source.list_sorted(&mut tasks, filter, keys)?;
+        let mut all = vec![];
+        if filter.is_some() {
+            self.source.list(&mut all, &None)?;
+        } else {
+            all = tasks.clone();
+        }
+        let deps = self.source.dependencies()?;
+        let blocked = deps::blocked(&all, &deps);
+        let now = Utc::now();
+        tasks.retain(|t| !t.is_snoozed(now));
+        if actionable {
+            tasks.retain(|t| t.status.is_open() && !blocked.contains(&t.uuid));
+            tasks = match sort.is_empty() {
+                true => deps::next_order(tasks, &all, &deps),
+                false => deps::blockers_first(tasks, &deps),
+            };
+        }
+
+        let tree = tree_order(tasks);
+        let labels = self.id_labels(tree.iter().map(|(t, _)| t))?;
+        Ok(tree
+            .into_iter()
+            .zip(labels)
+            .map(|((task, depth), id)| {
+                let mut name = match depth {
+                    0 => task.short.clone(),
+                    _ => format!("{}└ {}", "  ".repeat(depth - 1), task.short),
+                };
+                let (done, total) = progress(&all, &task);
+                if total > 0 {
+                    name = format!("{} [{}/{}]", name, done, total);
+                }
+                ListRow {
+                    id,
+                    name,
+                    blocked: blocked.contains(&task.uuid),
+                    task,
+                }
+            })
+            .collect())
+    }
+
+    /// How each task's id is displayed, saving the working ids if they are enabled.
+    fn id_labels<'a>(&mut self, tasks: impl Iterator<Item = &'a Task>) -> Result<Vec<String>> {
+        if !self.working_ids {
+            return Ok(tasks.map(|t| t.id.unwrap().to_string()).collect());
+        }
+        let mut open = vec![];
+        let labels = tasks
+            .map(|t| {
+                if t.status.is_open() {
+                    open.push(t.uuid);
+                    open.len().to_string()
+                } else {
+                    format!("#{}", t.id.unwrap())
+                }
+            })
+            .collect();
+        self.source.save_working_ids(&open)?;
+        Ok(labels)
+    }
+
+    /// All tasks of a priority, in their manual order.
+    pub fn bucket(&self, prio: &TaskPriority) -> Result<Vec<Task>> {
+        let mut tasks = vec![];
+        self.source.list(
+            &mut tasks,
+            &Some((FilterOptions::ByPriority, prio.to_string())),
+        )?;
+        self.sort(&mut tasks);
+        Ok(tasks)
+    }
+
+    /// Saves the order of `ids`, which all have to be of priority `prio`, by ranking them
+    /// 1..N. Tasks of another priority are moved to `prio`.
+    pub fn set_order(&mut self, prio: &TaskPriority, ids: &[u64]) -> Result<usize> {
+        self.transaction(|mgr| {
+            let mut updated = 0;
+            for (pos, id) in ids.iter().enumerate() {
+                let task = mgr.source.get(*id)?;
+                let rank = Some(pos as u32 + 1);
+                if task.rank != rank || task.prio != *prio {
+                    updated += mgr.set_status(
+                        *id,
+                        Task {
+                            rank,
+                            prio: prio.clone(),
+                            ..task
+                        },
+                    )?;
+                }
             }
+            Ok(updated)
+        })
+    }
+
+    /// Moves a task within its priority. Moving it before or after a task of another
+    /// priority gives it that priority.
+    pub fn move_task(&mut self, id: u64, placement: Placement) -> Result<usize> {
+        let task = self.source.get(id)?;
+        let prio = match placement {
+            Placement::Before(other) | Placement::After(other) => self.source.get(other)?.prio,
+            _ => task.prio,
         };
-        self.sort(&mut tasks);
-        self.print_task_list(&tasks);
+        let mut ids: Vec<u64> = self
+            .bucket(&prio)?
+            .iter()
+            .map(|t| t.id.unwrap())
+            .filter(|other| *other != id)
+            .collect();
+        let pos = match placement {
+            Placement::Top => 0,
+            Placement::Bottom => ids.len(),
+            Placement::Before(other) | Placement::After(other) => {
+                let pos = match ids.iter().position(|i| *i == other) {
+                    Some(pos) => pos,
+                    None => return Err(Error::not_found(other)),
+                };
+                if matches!(placement, Placement::After(_)) {
+                    pos + 1
+                } else {
+                    pos
+                }
+            }
+        };
+        ids.insert(pos, id);
+        self.set_order(&prio, &ids)
+    }
+
+    /// The `n` tasks to work on next, see `next::suggest`.
+    pub fn next(&self, n: usize) -> Result<Vec<Suggestion>> {
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        let deps = self.source.dependencies()?;
+        Ok(next::suggest(&all, &deps, n, Utc::now()))
+    }
+
+    /// Hides task `id` from `list` and `next` until `until`, or shows it again right away
+    /// if `until` is None.
+    pub fn snooze(&mut self, id: u64, until: Option<Timestamp>) -> Result<usize> {
+        let task = self.source.get(id)?;
+        self.set_status(
+            id,
+            Task {
+                snoozed_until: until,
+                ..task
+            },
+        )
+    }
+
+    /// Prints the tasks which are snoozed right now, the ones coming back first at the top.
+    pub fn list_snoozed(&mut self) -> Result<()> {
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        let now = Utc::now();
+        all.retain(|t| t.status.is_open() && t.is_snoozed(now));
+        all.sort_by_key(|t| t.snoozed_until);
+        let labels = self.id_labels(all.iter())?;
+        let rows: Vec<ListRow> = all
+            .into_iter()
+            .zip(labels)
+            .map(|(task, id)| ListRow {
+                id,
+                name: format!(
+                    "{} (until {})",
+                    task.short,
+                    task.snoozed_until.unwrap().format("%Y-%m-%d %H:%M")
+                ),
+                blocked: false,
+                task,
+            })
+            .collect();
+        self.print_task_list(&rows);
+        Ok(())
+    }
+
+    /// Starts working on task `id`, stopping whatever was worked on before. The task is
+    /// marked as started if it wasn't yet.
+    pub fn start_work(&mut self, id: u64) -> Result<usize> {
+        self.transaction(|mgr| {
+            let task = mgr.source.get(id)?;
+            let now = Utc::now();
+            mgr.source.end_sessions(now)?;
+            mgr.source.add_session(Session {
+                task: task.uuid,
+                start: now,
+                end: None,
+            })?;
+            if task.status == TaskStatus::Started && task.started.is_some() {
+                return Ok(1);
+            }
+            mgr.set_status(
+                id,
+                Task {
+                    status: TaskStatus::Started,
+                    started: task.started.or(Some(now)),
+                    ..task
+                },
+            )
+        })
+    }
+
+    /// Stops the running work session, returning the task and how long it ran.
+    pub fn stop_work(&mut self) -> Result<Option<(Task, Duration)>> {
+        let running = match self.active_work()? {
+            Some(running) => running,
+            None => return Ok(None),
+        };
+        let now = Utc::now();
+        self.source.end_sessions(now)?;
+        Ok(Some((running.0, now - running.1.start)))
+    }
+
+    /// The task being worked on right now, with its running session.
+    pub fn active_work(&self) -> Result<Option<(Task, Session)>> {
+        let session = match self
+            .source
+            .sessions()?
+            .into_iter()
+            .find(|s| s.end.is_none())
+        {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+        Ok(self
+            .source
+            .find_by_uuid(&session.task.to_string())?
+            .into_iter()
+            .next()
+            .map(|task| (task, session)))
+    }
+
+    /// Estimates compared to the time tracked on completed tasks.
+    pub fn estimate_report(&self) -> Result<estimate::Report> {
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        Ok(estimate::report(&all, &self.source.sessions()?, Utc::now()))
+    }
+
+    /// Open work, throughput and lead times, with weekly counts for the last `weeks` weeks.
+    pub fn stats(&self, weeks: usize) -> Result<Stats> {
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        Ok(stats::stats(&all, weeks, Utc::now()))
+    }
+
+    /// Time worked between `from` and `to`.
+    pub fn timesheet(&self, from: Timestamp, to: Timestamp) -> Result<Timesheet> {
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        Ok(time::timesheet(&all, &self.source.sessions()?, from, to))
+    }
+
+    /// Open tasks which sat in their priority for longer than `thresholds` allow. Priorities
+    /// not in `thresholds` use the saved thresholds, then the defaults.
+    pub fn review(&self, thresholds: &[Threshold], since_created: bool) -> Result<Vec<Stale>> {
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        let mut thresholds = thresholds.to_vec();
+        thresholds.extend(self.review_thresholds()?);
+        Ok(review::stale(&all, &thresholds, since_created, Utc::now()))
+    }
+
+    /// The review thresholds kept by `save_review_thresholds`.
+    pub fn review_thresholds(&self) -> Result<Vec<Threshold>> {
+        match self.source.setting(REVIEW_THRESHOLDS)? {
+            Some(saved) => saved.split(',').map(str::parse).collect(),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Keeps `thresholds` for later reviews, replacing the saved ones of the same priorities.
+    pub fn save_review_thresholds(&mut self, thresholds: &[Threshold]) -> Result<()> {
+        let mut saved = self.review_thresholds()?;
+        saved.retain(|s| thresholds.iter().all(|t| t.prio != s.prio));
+        saved.extend_from_slice(thresholds);
+        let saved: Vec<String> = saved.iter().map(|t| t.to_string()).collect();
+        self.source
+            .save_setting(REVIEW_THRESHOLDS, &saved.join(","))
+    }
+
+    /// Applies the outcome of reviewing task `id`. Keeping a task restarts its clock.
+    pub fn apply_review(&mut self, id: u64, action: Action) -> Result<usize> {
+        let task = self.source.get(id)?;
+        match review::apply(&task, action, Utc::now()) {
+            Some(task) => self.set_status(id, task),
+            None => Ok(0),
+        }
+    }
+
+    /// Moves task `id` to another priority, at the end of its manual order.
+    pub fn set_priority(&mut self, id: u64, prio: TaskPriority) -> Result<usize> {
+        let task = self.source.get(id)?;
+        if task.prio == prio {
+            return Ok(0);
+        }
+        self.set_status(
+            id,
+            Task {
+                prio,
+                rank: None,
+                ..task
+            },
+        )
+    }
+
+    /// Records that task `id` can't be worked on before task `blocker` is done.
+    pub fn add_dependency(&mut self, id: u64, blocker: u64) -> Result<usize> {
+        let dep = Dependency {
+            task: self.source.get(id)?.uuid,
+            blocker: self.source.get(blocker)?.uuid,
+        };
+        if id == blocker || deps::creates_cycle(&self.source.dependencies()?, &dep) {
+            return Err(Error::DependencyCycle);
+        }
+        self.source.add_dependency(dep)
     }
 
-    pub fn remove(&mut self, id: u64) -> Result<usize> {
-        self.source.remove(id)
+    pub fn remove_dependency(&mut self, id: u64, blocker: u64) -> Result<usize> {
+        let dep = Dependency {
+            task: self.source.get(id)?.uuid,
+            blocker: self.source.get(blocker)?.uuid,
+        };
+        self.source.remove_dependency(dep)
+    }
+
+    /// Tasks which task `id` waits for, and tasks waiting for task `id`.
+    fn dependency_neighbours(&self, task: &Task) -> Result<(Vec<Task>, Vec<Task>)> {
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        let deps = self.source.dependencies()?;
+        let find = |uuid: &Uuid| all.iter().find(|t| t.uuid == *uuid).cloned();
+        let blockers = deps
+            .iter()
+            .filter(|d| d.task == task.uuid)
+            .filter_map(|d| find(&d.blocker))
+            .collect();
+        let waiting = deps
+            .iter()
+            .filter(|d| d.blocker == task.uuid)
+            .filter_map(|d| find(&d.task))
+            .collect();
+        Ok((blockers, waiting))
+    }
+
+    /// Direct subtasks of a task.
+    pub fn subtasks(&self, id: u64) -> Result<Vec<Task>> {
+        let task = self.source.get(id)?;
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        Ok(all
+            .into_iter()
+            .filter(|t| t.parent == Some(task.uuid))
+            .collect())
+    }
+
+    /// Subtasks at any depth which are still open.
+    pub fn open_subtasks(&self, id: u64) -> Result<Vec<Task>> {
+        let task = self.source.get(id)?;
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        Ok(descendants(&all, &task)
+            .into_iter()
+            .filter(|t| t.status.is_open())
+            .collect())
+    }
+
+    /// Removes several tasks, skipping those already removed as a subtask of another.
+    pub fn remove_all(&mut self, ids: &[u64], subtasks: Subtasks) -> Result<usize> {
+        self.transaction(|mgr| {
+            let mut removed = 0;
+            for &id in ids {
+                if mgr.source.get(id).is_ok() {
+                    removed += mgr.remove(id, subtasks)?;
+                }
+            }
+            Ok(removed)
+        })
+    }
+
+    /// Removes a task, `subtasks` decides what happens to its subtasks.
+    pub fn remove(&mut self, id: u64, subtasks: Subtasks) -> Result<usize> {
+        self.transaction(|mgr| {
+            let task = mgr.source.get(id)?;
+            let mut removed = 0;
+            match subtasks {
+                Subtasks::Remove => {
+                    let mut all = vec![];
+                    mgr.source.list(&mut all, &None)?;
+                    for t in descendants(&all, &task) {
+                        removed += mgr.source.remove(t.id.unwrap())?;
+                    }
+                }
+                Subtasks::Reparent => {
+                    for child in mgr.subtasks(id)? {
+                        mgr.set_status(
+                            child.id.unwrap(),
+                            Task {
+                                parent: task.parent,
+                                ..child
+                            },
+                        )?;
+                    }
+                }
+            }
+            Ok(removed + mgr.source.remove(id)?)
+        })
     }
 
+    /// Saves the changed `task`, recording when it was started and completed. Completing a
+    /// recurring task creates the next task of its series, which takes the recurrence over.
     pub fn set_status(&mut self, id: u64, task: Task) -> Result<usize> {
         log::debug!("{:x?}", task);
-        self.source.update_task(id, task)
+        self.transaction(|mgr| {
+            let (task, next) = mgr.prepare_update(id, task)?;
+            let updated = mgr.source.update_task(id, task)?;
+            if let Some(next) = next {
+                mgr.source.write_task(next)?;
+            }
+            Ok(updated)
+        })
+    }
+
+    /// Stamps an update of task `id`: when it was modified, started and completed.
+    /// Completing a recurring task also gives the next task of its series.
+    fn prepare_update(&self, id: u64, mut task: Task) -> Result<(Task, Option<Task>)> {
+        task.modified = Utc::now();
+        let old = self.source.get(id)?;
+        if task.status == TaskStatus::Started && task.started.is_none() {
+            task.started = Some(task.modified);
+        }
+        let completed = task.status == TaskStatus::Completed && old.status != TaskStatus::Completed;
+        if completed {
+            task.completed = Some(task.modified);
+        } else if task.status.is_open() {
+            task.completed = None;
+        }
+        let next = match completed {
+            true => recur::next_instance(&task, task.modified),
+            false => None,
+        };
+        if next.is_some() {
+            task.recurrence = None;
+            task.series = Some(task.series.unwrap_or(task.uuid));
+        }
+        Ok((task, next))
+    }
+
+    /// All tasks matching `filter`, in list order.
+    pub fn select(&self, filter: &Filter) -> Result<Vec<Task>> {
+        let mut all = vec![];
+        self.source
+            .list_sorted(&mut all, &None, &sort::DEFAULT_ORDER)?;
+        Ok(filter.select(&all))
+    }
+
+    /// Applies `change` to all `tasks` at once, either all of them are updated or none.
+    pub fn update_all(&mut self, tasks: &[Task], change: impl Fn(Task) -> Task) -> Result<usize> {
+        self.transaction(|mgr| {
+            let mut updated = 0;
+            for task in tasks {
+                updated += mgr.set_status(task.id.unwrap(), change(task.clone()))?;
+            }
+            Ok(updated)
+        })
+    }
+
+    /// Sets the status of all `tasks`.
+    pub fn set_status_all(&mut self, tasks: &[Task], status: TaskStatus) -> Result<usize> {
+        self.update_all(tasks, |task| Task {
+            status: status.clone(),
+            ..task
+        })
+    }
+
+    /// Moves all `tasks` to `prio`, at the end of its manual order.
+    pub fn set_priority_all(&mut self, tasks: &[Task], prio: TaskPriority) -> Result<usize> {
+        self.update_all(tasks, |task| Task {
+            prio: prio.clone(),
+            rank: None,
+            ..task
+        })
+    }
+
+    /// Makes task `id` recur, or stops its series if `rule` is None.
+    pub fn set_recurrence(&mut self, id: u64, rule: Option<Recurrence>) -> Result<usize> {
+        let task = self.source.get(id)?;
+        if rule.is_none() {
+            return self.stop_series(id);
+        }
+        self.set_status(
+            id,
+            Task {
+                recurrence: rule,
+                ..task
+            },
+        )
+    }
+
+    /// Open tasks of every running series, the next one due first.
+    pub fn recurring(&self) -> Result<Vec<Task>> {
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        all.retain(|t| t.status.is_open() && t.recurrence.is_some());
+        all.sort_by_key(|t| t.due);
+        Ok(all)
+    }
+
+    /// Ends the series task `id` belongs to: its open tasks stay, but no new ones are created.
+    pub fn stop_series(&mut self, id: u64) -> Result<usize> {
+        self.transaction(|mgr| {
+            let task = mgr.source.get(id)?;
+            let series = task.series.unwrap_or(task.uuid);
+            let mut all = vec![];
+            mgr.source.list(&mut all, &None)?;
+            let mut updated = 0;
+            for t in all
+                .into_iter()
+                .filter(|t| t.recurrence.is_some() && t.series.unwrap_or(t.uuid) == series)
+            {
+                updated += mgr.set_status(
+                    t.id.unwrap(),
+                    Task {
+                        recurrence: None,
+                        ..t
+                    },
+                )?;
+            }
+            Ok(updated)
+        })
+    }
+
+    /// All open tasks, snoozed ones included, by priority and manual rank.
+    pub fn open_tasks(&self) -> Result<Vec<Task>> {
+        let mut tasks = vec![];
+        self.source
+            .list_sorted(&mut tasks, &None, &sort::DEFAULT_ORDER)?;
+        tasks.retain(|t| t.status.is_open());
+        Ok(tasks)
+    }
+
+    /// Tasks matching `query`, best matches first.
+    pub fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        self.source.search(query)
     }
 
-    pub fn get_task(&mut self, id: u64) -> Result<Task> {
+    pub fn get_task(&self, id: u64) -> Result<Task> {
         self.source.get(id)
     }
 
-    fn sort(&self, list: &mut Vec<Task>) {
-        list.sort_by(|a, b| a.prio.cmp(&b.prio))
+    /// Resolves a task reference given by the user: a local id (`12` or `#12`), a working id
+    /// if enabled, or a unique prefix of the task's uuid.
+    pub fn resolve_id(&self, reference: &str) -> Result<u64> {
+        let reference = reference.trim().to_lowercase();
+        if let Some(id) = reference.strip_prefix('#') {
+            let id = id.parse::<u64>()?;
+            return self.source.get(id).map(|_| id);
+        }
+        if let Ok(id) = reference.parse::<u64>() {
+            if self.working_ids {
+                return self.resolve_working_id(id);
+            }
+            if self.source.get(id).is_ok() {
+                return Ok(id);
+            }
+        }
+        if reference.len() < MIN_UUID_PREFIX_LEN
+            || !reference.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+        {
+            return Err(Error::not_found(reference));
+        }
+        let matches = self.source.find_by_uuid(&reference)?;
+        match matches.as_slice() {
+            [] => Err(Error::not_found(reference)),
+            [task] => Ok(task.id.unwrap()),
+            _ => Err(Error::AmbiguousTaskId(reference)),
+        }
+    }
+
+    fn resolve_working_id(&self, working_id: u64) -> Result<u64> {
+        let uuids = self.source.working_ids()?;
+        let uuid = match working_id
+            .checked_sub(1)
+            .and_then(|i| uuids.get(i as usize))
+        {
+            Some(uuid) => uuid.to_string(),
+            None => return Err(Error::not_found(working_id)),
+        };
+        match self.source.find_by_uuid(&uuid)?.first() {
+            Some(task) => Ok(task.id.unwrap()),
+            None => Err(Error::not_found(working_id)),
+        }
+    }
+
+    /// Marks a task completed, and optionally all of its open subtasks too.
+    pub fn done(&mut self, id: u64, with_subtasks: bool) -> Result<usize> {
+        self.transaction(|mgr| {
+            let mut updated = 0;
+            if with_subtasks {
+                for child in mgr.open_subtasks(id)? {
+                    updated += mgr.done(child.id.unwrap(), false)?;
+                }
+            }
+            let mut task = mgr.source.get(id)?;
+            task.status = TaskStatus::Completed;
+            Ok(updated + mgr.set_status(id, task)?)
+        })
+    }
+
+    pub fn sync(&mut self, remote: &str, policy: MergePolicy) -> Result<SyncReport> {
+        self.source.sync(remote, policy)
+    }
+
+    /// Two-way sync with another datasource, tasks are matched by their uuid.
+    /// `resolve` decides which version wins for tasks which were changed on both sides.
+    pub fn sync_with(
+        &mut self,
+        other: &mut dyn Datasource,
+        resolve: &mut dyn FnMut(&Task, &Task) -> Result<MergePolicy>,
+    ) -> Result<SyncReport> {
+        self.transaction(|mgr| {
+            source::with_transaction(other, |other| mgr.reconcile_with(other, resolve))
+        })
     }
 
-    fn print_task_list(&self, tasks: &Vec<Task>) {
+    fn reconcile_with(
+        &mut self,
+        other: &mut dyn Datasource,
+        resolve: &mut dyn FnMut(&Task, &Task) -> Result<MergePolicy>,
+    ) -> Result<SyncReport> {
+        let mut ours = vec![];
+        self.source.list(&mut ours, &None)?;
+        let mut theirs = vec![];
+        other.list(&mut theirs, &None)?;
+        let reconcile = merge::reconcile(
+            &ours,
+            &theirs,
+            &self.source.tombstones()?,
+            &other.tombstones()?,
+        );
+
+        let mut report = SyncReport::default();
+        for id in reconcile.remove_ours {
+            report.removed += self.source.remove(id)?;
+        }
+        for id in reconcile.remove_theirs {
+            report.pushed += other.remove(id)?;
+        }
+        for task in reconcile.ours {
+            self.source.write_task(task)?;
+            report.added += 1;
+        }
+        for task in reconcile.theirs {
+            other.write_task(task)?;
+            report.pushed += 1;
+        }
+        for id in reconcile.kept {
+            report.conflicts.push(Conflict {
+                id,
+                fields: vec![],
+                resolution: "removed by them but edited by us, kept".to_string(),
+            });
+        }
+        for uuid in reconcile.restored {
+            for task in self.source.find_by_uuid(&uuid.to_string())? {
+                report.conflicts.push(Conflict {
+                    id: task.id.unwrap(),
+                    fields: vec![],
+                    resolution: "removed by us but edited by them, restored".to_string(),
+                });
+            }
+        }
+        // dependencies can't be told apart from removed ones without a history, so both
+        // sides end up with all of them
+        let (our_deps, their_deps) = (self.source.dependencies()?, other.dependencies()?);
+        for dep in their_deps.iter().filter(|d| !our_deps.contains(d)) {
+            self.source.add_dependency(*dep)?;
+        }
+        for dep in our_deps.iter().filter(|d| !their_deps.contains(d)) {
+            other.add_dependency(*dep)?;
+        }
+        let (our_sessions, their_sessions) = (self.source.sessions()?, other.sessions()?);
+        for session in merge::merge_sessions(&our_sessions, &their_sessions) {
+            self.source.add_session(session)?;
+        }
+        for session in merge::merge_sessions(&their_sessions, &our_sessions) {
+            other.add_session(session)?;
+        }
+        for (o, t) in reconcile.conflicts {
+            let fields = merge::differing_fields(&o, &t);
+            let (id, their_id) = (o.id.unwrap(), t.id.unwrap());
+            // without a common history there is no telling which side changed what, so the
+            // whole task is taken from one side and the values of the other are reported
+            let (resolution, (side, lost)) = match resolve(&o, &t)?.side(&o, &t) {
+                MergePolicy::Ours => {
+                    other.update_task(
+                        their_id,
+                        Task {
+                            id: t.id,
+                            ..o.clone()
+                        },
+                    )?;
+                    report.pushed += 1;
+                    ("kept our version", ("their", &t))
+                }
+                _ => {
+                    self.source.update_task(
+                        id,
+                        Task {
+                            id: o.id,
+                            ..t.clone()
+                        },
+                    )?;
+                    report.updated += 1;
+                    ("took their version", ("our", &o))
+                }
+            };
+            let values: Vec<String> = fields
+                .iter()
+                .filter(|f| **f != "modified")
+                .map(|f| format!("{} {}", f, merge::field_value(lost, f)))
+                .collect();
+            let resolution = match values.is_empty() {
+                true => resolution.to_string(),
+                false => format!("{}, dropped {} {}", resolution, side, values.join(", ")),
+            };
+            report.conflicts.push(Conflict {
+                id,
+                fields,
+                resolution,
+            });
+        }
+        Ok(report)
+    }
+
+    fn sort(&self, list: &mut [Task]) {
+        sort::sort(list, &sort::DEFAULT_ORDER)
+    }
+
+    fn print_task_list(&self, rows: &[ListRow]) {
         println!("==================================================================================================================");
         println!("Current task list");
         println!("------------------------------------------------------------------------------------------------------------------");
-        println!("| id\t  | task{} | priority{} | status{} | description                                                             |", " ".repeat(TASK_SHORT_LEN - String::from("task").len()), " ".repeat(25 - String::from("priority").len()), " ".repeat(20-String::from("status").len()));
+        println!("| id\t  | task{} | priority{} | estimate | status{} | description                                                             |", " ".repeat(TASK_SHORT_LEN - String::from("task").len()), " ".repeat(25 - String::from("priority").len()), " ".repeat(20-String::from("status").len()));
         println!("------------------------------------------------------------------------------------------------------------------");
-        for t in tasks {
+        for row in rows {
+            let t = &row.task;
             let desc_display = t.desc.clone();
             let parts: Vec<&str> = desc_display.split('\n').collect();
-            let show = parts[0];
-            if show.len() > TASK_DESC_LEN {
-                show.to_string().truncate(TASK_DESC_LEN)
-            };
+            let show: String = parts[0].chars().take(TASK_DESC_LEN).collect();
+            let mut status = t.status.to_string();
+            if row.blocked {
+                status += ", blocked";
+            }
             println!(
-                "|  {:x?}\t  | {:.30}{} | {:.30}{} | {:.20}{} | {} ",
-                t.id.unwrap(),
-                t.short,
-                " ".repeat(30 - t.short.len()),
-                t.prio.to_string(),
-                " ".repeat(25 - t.prio.to_string().len()),
-                t.status.to_string(),
-                " ".repeat(20 - t.status.to_string().len()),
+                "|  {}\t  | {} | {} | {} | {} | {} ",
+                row.id,
+                pad(&row.name, TASK_SHORT_LEN),
+                pad(&t.prio.to_string(), 25),
+                pad(&t.estimate.map(|e| e.to_string()).unwrap_or_default(), 8),
+                pad(&status, 20),
                 show,
             );
         }
+
+        // open work per priority
+        let mut totals: Vec<(TaskPriority, estimate::Total)> = vec![];
+        for prio in TaskPriority::iter() {
+            let mut total = estimate::Total::default();
+            for e in rows
+                .iter()
+                .filter(|r| r.task.prio == prio && r.task.status.is_open())
+                .filter_map(|r| r.task.estimate.as_ref())
+            {
+                total.add(e);
+            }
+            if !total.is_empty() {
+                totals.push((prio, total));
+            }
+        }
+        if !totals.is_empty() {
+            println!("------------------------------------------------------------------------------------------------------------------");
+            for (prio, total) in totals {
+                println!("  {}: {} estimated", pad(&prio.to_string(), 25), total);
+            }
+        }
     }
 
     pub(crate) fn show(&self, id: u64) -> Result<()> {
@@ -100,22 +942,160 @@ impl TaskManager {
         println!(" Show info for task: {}", task.id.unwrap());
         println!("------------------------------------------------------------------------------------------------------------------");
         println!(" ID: {}", task.id.unwrap());
+        println!(" UUID: {}", task.uuid);
         println!(" Task: {}", task.short);
         println!(" Description: {}", task.desc);
         println!(" Priority: {}", task.prio.to_string());
         println!(" Status: {}", task.status.to_string());
+        if task.is_snoozed(Utc::now()) {
+            println!(
+                " Snoozed until: {}",
+                task.snoozed_until.unwrap().format("%Y-%m-%d %H:%M")
+            );
+        }
+        if let Some(rule) = task.recurrence {
+            println!(" Recurs: {}", rule);
+        }
+        if let Some(estimate) = task.estimate {
+            println!(" Estimate: {}", estimate);
+        }
+        let sessions = self.source.sessions()?;
+        if sessions.iter().any(|s| s.task == task.uuid) {
+            let running = sessions
+                .iter()
+                .any(|s| s.task == task.uuid && s.end.is_none());
+            println!(
+                " Time spent: {}{}",
+                time::format_duration(time::spent(&task, &sessions, Utc::now())),
+                if running { " (running)" } else { "" }
+            );
+        }
+        if let Some(parent) = task.parent {
+            match self.source.find_by_uuid(&parent.to_string())?.first() {
+                Some(p) => println!(" Parent: #{} {}", p.id.unwrap(), p.short),
+                None => println!(" Parent: {} (missing)", parent),
+            }
+        }
+
+        let (blockers, waiting) = self.dependency_neighbours(&task)?;
+        for t in blockers {
+            println!(
+                " Blocked by: #{} {} ({})",
+                t.id.unwrap(),
+                t.short,
+                t.status.to_string()
+            );
+        }
+        for t in waiting {
+            println!(" Blocks: #{} {}", t.id.unwrap(), t.short);
+        }
+
+        let mut all = vec![];
+        self.source.list(&mut all, &None)?;
+        let (done, total) = progress(&all, &task);
+        if total > 0 {
+            println!(" Subtasks: {}/{} subtasks completed", done, total);
+            for (t, depth) in tree_order(descendants(&all, &task)) {
+                println!(
+                    "   {}- #{} {} ({})",
+                    "  ".repeat(depth),
+                    t.id.unwrap(),
+                    t.short,
+                    t.status.to_string()
+                );
+            }
+        }
 
         Ok(())
     }
 }
 
+/// Where to move a task within its priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    Top,
+    Bottom,
+    Before(u64),
+    After(u64),
+}
+
+/// What happens to the subtasks of a removed task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subtasks {
+    /// remove them, including their own subtasks
+    Remove,
+    /// move them up to the parent of the removed task
+    Reparent,
+}
+
+struct ListRow {
+    id: String,
+    name: String,
+    blocked: bool,
+    task: Task,
+}
+
+/// Orders tasks so that subtasks directly follow their parent, keeping the order otherwise.
+/// Tasks whose parent is not part of `tasks` are shown at the top level.
+fn tree_order(tasks: Vec<Task>) -> Vec<(Task, usize)> {
+    fn push_subtree(task: &Task, depth: usize, tasks: &[Task], rows: &mut Vec<(Task, usize)>) {
+        rows.push((task.clone(), depth));
+        for child in tasks.iter().filter(|t| t.parent == Some(task.uuid)) {
+            push_subtree(child, depth + 1, tasks, rows);
+        }
+    }
+
+    let mut rows = vec![];
+    for root in tasks.iter().filter(|t| {
+        t.parent
+            .is_none_or(|parent| !tasks.iter().any(|p| p.uuid == parent))
+    }) {
+        push_subtree(root, 0, &tasks, &mut rows);
+    }
+    // broken parent chains which loop back on themselves would never show up otherwise
+    for t in &tasks {
+        if !rows.iter().any(|(r, _)| r.uuid == t.uuid) {
+            rows.push((t.clone(), 0));
+        }
+    }
+    rows
+}
+
+/// Subtasks of `task` at any depth.
+fn descendants(all: &[Task], task: &Task) -> Vec<Task> {
+    let mut found: Vec<Task> = vec![];
+    let mut queue = vec![task.uuid];
+    while let Some(uuid) = queue.pop() {
+        for t in all.iter().filter(|t| t.parent == Some(uuid)) {
+            if t.uuid != task.uuid && !found.iter().any(|f| f.uuid == t.uuid) {
+                queue.push(t.uuid);
+                found.push(t.clone());
+            }
+        }
+    }
+    found
+}
+
+/// Closed and total number of direct subtasks of `task`.
+fn progress(all: &[Task], task: &Task) -> (usize, usize) {
+    let children: Vec<&Task> = all.iter().filter(|t| t.parent == Some(task.uuid)).collect();
+    let done = children.iter().filter(|t| !t.status.is_open()).count();
+    (done, children.len())
+}
+
+/// Cuts or pads `s` to exactly `width` characters.
+fn pad(s: &str, width: usize) -> String {
+    let cut: String = s.chars().take(width).collect();
+    let len = cut.chars().count();
+    format!("{}{}", cut, " ".repeat(width - len))
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
     use tempfile;
 
     use super::*;
-    use crate::task::{TaskPriority, TaskStatus};
 
     #[test]
     fn create_db() -> Result<()> {
@@ -126,14 +1106,32 @@ mod tests {
 
         // because we created the tempfile already, it should actually already exist...
         assert!(fs::exists(path).expect("this is a temp file and should exist"));
-        let _mgr = TaskManager::new(ds, &tmpfile.to_string());
+        let _mgr = TaskManager::new(ds, &tmpfile)?;
         // should still be there
         assert!(fs::exists(path).expect("this file should now exist"));
 
         // should fail, non-existing path
-        let should_panic =
-            std::panic::catch_unwind(|| TaskManager::new(ds, &"/root/no_perms.sql".to_string()));
-        assert!(should_panic.is_err());
+        let dir = tempfile::tempdir()?;
+        let missing = dir.path().join("no_such_dir").join("tasks.sql");
+        assert!(matches!(
+            TaskManager::new(ds, &missing.to_string_lossy()),
+            Err(Error::Storage { .. })
+        ));
+        assert!(!fs::exists(dir.path().join("no_such_dir"))?);
+        Ok(())
+    }
+
+    #[test]
+    fn create_db_in_broken_repository() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join(".git"), "gitdir: no_such_dir")?;
+        let path = dir.path().join("tasks.sql");
+        assert!(matches!(
+            TaskManager::builder(Datasources::SqlLite, &path.to_string_lossy())
+                .git(true)
+                .build(),
+            Err(Error::Storage { .. })
+        ));
         Ok(())
     }
 
@@ -146,9 +1144,9 @@ mod tests {
 
         // because we created the tempfile already, it should actually already exist...
         assert!(fs::exists(path).expect("this is a temp file and should exist"));
-        let mut mgr = TaskManager::new(ds, &tmpfile.to_string());
+        let mut mgr = TaskManager::new(ds, &tmpfile)?;
         // listing should just work
-        mgr.list(&None);
+        mgr.list(&None, &[])?;
 
         let t = Task::new(
             "test42".to_string(),
@@ -157,7 +1155,7 @@ mod tests {
         );
         // add a new task
         assert_eq!(mgr.add(t).ok(), Some(1));
-        assert!(mgr.get_task(99).is_err());
+        assert!(matches!(mgr.get_task(99), Err(Error::NotFound { .. })));
         // get it and compare
         let mut ctrl = mgr.get_task(1);
         assert!(ctrl.is_ok());
@@ -168,6 +1166,14 @@ mod tests {
         assert_eq!(ctrl_task.prio, TaskPriority::UrgentAndImportant);
         assert!(matches!(ctrl_task.status, TaskStatus::Created));
 
+        // resolve by id or uuid prefix
+        let uuid = ctrl_task.uuid.to_string();
+        assert_eq!(mgr.resolve_id("1").ok(), Some(1));
+        assert_eq!(mgr.resolve_id(&uuid[..8]).ok(), Some(1));
+        assert_eq!(mgr.resolve_id(&uuid.to_uppercase()).ok(), Some(1));
+        assert!(mgr.resolve_id(&uuid[..3]).is_err());
+        assert!(mgr.resolve_id("2").is_err());
+
         // update status
         let mut update = ctrl_task.clone();
         update.status = TaskStatus::WontDo;
@@ -181,9 +1187,535 @@ mod tests {
         assert_eq!(ctrl_task.prio, TaskPriority::UrgentAndImportant);
         assert!(matches!(ctrl_task.status, TaskStatus::WontDo));
 
-        assert_eq!(mgr.remove(1).ok(), Some(1));
+        assert_eq!(mgr.remove(1, Subtasks::Remove).ok(), Some(1));
         ctrl = mgr.get_task(1);
         assert!(ctrl.is_err());
         Ok(())
     }
+
+    #[test]
+    fn sync_databases() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let laptop = dir.path().join("laptop.sql").to_string_lossy().to_string();
+        let workstation = dir
+            .path()
+            .join("workstation.sql")
+            .to_string_lossy()
+            .to_string();
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &laptop)?;
+        let mut other = source::open(Datasources::SqlLite, &workstation)?;
+
+        mgr.add(Task::new(
+            "laptop".to_string(),
+            "".to_string(),
+            TaskPriority::ToDo,
+        ))?;
+        other.write_task(Task::new(
+            "unrelated".to_string(),
+            "".to_string(),
+            TaskPriority::Watch,
+        ))?;
+        let report = mgr.sync_with(other.as_mut(), &mut |_, _| Ok(MergePolicy::Newest))?;
+        assert_eq!((report.added, report.pushed), (1, 1));
+
+        // rowids differ between the databases, uuids don't
+        let shared = mgr.get_task(1)?;
+        let mut theirs = other.get(2)?;
+        assert_eq!(shared.uuid, theirs.uuid);
+
+        theirs.status = TaskStatus::Completed;
+        theirs.modified = Utc::now();
+        other.update_task(2, theirs)?;
+        let mut ours = shared.clone();
+        ours.prio = TaskPriority::SomeDay;
+        ours.modified = shared.modified;
+        mgr.source.update_task(1, ours)?;
+
+        let report = mgr.sync_with(other.as_mut(), &mut |_, _| Ok(MergePolicy::Newest))?;
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(
+            report.conflicts[0].fields,
+            vec!["status", "prio", "modified"]
+        );
+        assert_eq!(
+            report.conflicts[0].resolution,
+            "took their version, dropped our status Created, prio Some Day"
+        );
+        assert_eq!(mgr.get_task(1)?.status, TaskStatus::Completed);
+        assert_eq!(mgr.get_task(1)?.prio, TaskPriority::ToDo);
+
+        let report = mgr.sync_with(other.as_mut(), &mut |_, _| Ok(MergePolicy::Ours))?;
+        assert!(report.conflicts.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_removed_tasks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &path("laptop.sql"))?;
+        let mut other = source::open(Datasources::Json, &path("workstation.json"))?;
+        for name in ["gone", "edited", "theirs"] {
+            mgr.add(Task::new(
+                name.to_string(),
+                "".to_string(),
+                TaskPriority::ToDo,
+            ))?;
+        }
+        mgr.sync_with(other.as_mut(), &mut |_, _| Ok(MergePolicy::Newest))?;
+
+        // removed here and left alone there: removed there as well
+        mgr.remove(1, Subtasks::Remove)?;
+        // removed here, edited there afterwards: restored
+        mgr.remove(2, Subtasks::Remove)?;
+        let mut edited = other.get(2)?;
+        edited.prio = TaskPriority::UrgentAndImportant;
+        edited.modified = Utc::now();
+        other.update_task(2, edited)?;
+        // removed there: removed here
+        other.remove(3)?;
+
+        let report = mgr.sync_with(other.as_mut(), &mut |_, _| Ok(MergePolicy::Newest))?;
+        assert_eq!(report.removed, 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(
+            report.conflicts[0].resolution,
+            "removed by us but edited by them, restored"
+        );
+        let mut ours = vec![];
+        mgr.source.list(&mut ours, &None)?;
+        let mut theirs = vec![];
+        other.list(&mut theirs, &None)?;
+        for tasks in [ours, theirs] {
+            let names: Vec<_> = tasks.iter().map(|t| t.short.as_str()).collect();
+            assert_eq!(names, vec!["edited"]);
+            assert_eq!(tasks[0].prio, TaskPriority::UrgentAndImportant);
+        }
+
+        // nothing comes back on the next sync
+        let report = mgr.sync_with(other.as_mut(), &mut |_, _| Ok(MergePolicy::Newest))?;
+        assert_eq!((report.added, report.removed, report.pushed), (0, 0, 0));
+        assert!(report.conflicts.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn saved_review_thresholds() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for (ds, file) in [
+            (Datasources::SqlLite, "t.sql"),
+            (Datasources::Json, "t.json"),
+        ] {
+            let path = dir.path().join(file).to_string_lossy().to_string();
+            let mut mgr = TaskManager::new(ds, &path)?;
+            let mut task = Task::new("rotting".to_string(), "".to_string(), TaskPriority::SomeDay);
+            task.modified = Utc::now() - chrono::Duration::days(20);
+            mgr.add(task)?;
+            assert!(mgr.review(&[], false)?.is_empty());
+
+            let threshold = |s: &str| s.parse::<Threshold>().unwrap();
+            mgr.save_review_thresholds(&[threshold("some-day=10"), threshold("watch=3")])?;
+            mgr.save_review_thresholds(&[threshold("some-day=15")])?;
+            let mgr = TaskManager::new(ds, &path)?;
+            assert_eq!(
+                mgr.review_thresholds()?,
+                vec![threshold("watch=3"), threshold("some-day=15")]
+            );
+            assert_eq!(mgr.review(&[], false)?.len(), 1);
+            // flags win over saved thresholds
+            assert!(mgr.review(&[threshold("some-day=30")], false)?.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn working_ids() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        for (name, prio) in [
+            ("later", TaskPriority::SomeDay),
+            ("closed", TaskPriority::UrgentAndImportant),
+            ("now", TaskPriority::UrgentAndImportant),
+        ] {
+            mgr.add(Task::new(name.to_string(), "".to_string(), prio))?;
+        }
+        mgr.done(2, false)?;
+        mgr.use_working_ids(true);
+        // nothing listed yet
+        assert!(mgr.resolve_id("1").is_err());
+        mgr.list(&None, &[])?;
+        assert_eq!(mgr.resolve_id("1")?, 3);
+        assert_eq!(mgr.resolve_id("2")?, 1);
+        assert!(mgr.resolve_id("3").is_err());
+        assert_eq!(mgr.resolve_id("#2")?, 2);
+
+        // numbers stay valid until the next list
+        mgr.done(3, false)?;
+        assert_eq!(mgr.resolve_id("2")?, 1);
+        mgr.list(&None, &[])?;
+        assert_eq!(mgr.resolve_id("1")?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn subtasks() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        let parent = Task::new("release".to_string(), "".to_string(), TaskPriority::ToDo);
+        let uuid = parent.uuid;
+        mgr.add(parent)?;
+        for name in ["build", "test"] {
+            let mut child = Task::new(name.to_string(), "".to_string(), TaskPriority::ToDo);
+            child.parent = Some(uuid);
+            mgr.add(child)?;
+        }
+        let mut grandchild = Task::new("unit".to_string(), "".to_string(), TaskPriority::ToDo);
+        grandchild.parent = Some(mgr.get_task(3)?.uuid);
+        mgr.add(grandchild)?;
+        mgr.add(Task::new(
+            "other".to_string(),
+            "".to_string(),
+            TaskPriority::UrgentAndImportant,
+        ))?;
+
+        let rows = mgr.list_rows(&None, &[], false)?;
+        let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "other",
+                "release [0/2]",
+                "└ build",
+                "└ test [0/1]",
+                "  └ unit"
+            ]
+        );
+
+        mgr.done(2, false)?;
+        assert_eq!(mgr.open_subtasks(1)?.len(), 2);
+        mgr.done(1, true)?;
+        assert!(mgr.open_subtasks(1)?.is_empty());
+        assert_eq!(mgr.list_rows(&None, &[], false)?[1].name, "release [2/2]");
+
+        // removing keeps the grandchild when reparenting
+        mgr.remove(3, Subtasks::Reparent)?;
+        assert_eq!(mgr.get_task(4)?.parent, Some(uuid));
+        // the subtask picked as well is already gone with its parent
+        assert_eq!(mgr.remove_all(&[1, 4], Subtasks::Remove)?, 3);
+        assert!(mgr.get_task(4).is_err());
+        assert!(mgr.get_task(5).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn dependencies() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        for (name, prio) in [
+            ("report", TaskPriority::UrgentAndImportant),
+            ("data", TaskPriority::SomeDay),
+            ("other", TaskPriority::ToDo),
+        ] {
+            mgr.add(Task::new(name.to_string(), "".to_string(), prio))?;
+        }
+        mgr.add_dependency(1, 2)?;
+        assert!(matches!(
+            mgr.add_dependency(2, 1),
+            Err(Error::DependencyCycle)
+        ));
+        assert!(matches!(
+            mgr.add_dependency(1, 1),
+            Err(Error::DependencyCycle)
+        ));
+
+        let rows = mgr.list_rows(&None, &[], false)?;
+        assert!(rows[0].blocked);
+        let rows = mgr.list_rows(&None, &[], true)?;
+        let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["data", "other"]);
+
+        mgr.done(2, false)?;
+        let rows = mgr.list_rows(&None, &[], true)?;
+        assert_eq!(rows[0].name, "report");
+        assert!(!rows[0].blocked);
+
+        assert_eq!(mgr.remove_dependency(1, 2)?, 1);
+        mgr.add_dependency(2, 1)?;
+        Ok(())
+    }
+
+    #[test]
+    fn manual_order() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        for name in ["a", "b", "c"] {
+            mgr.add(Task::new(
+                name.to_string(),
+                "".to_string(),
+                TaskPriority::ToDo,
+            ))?;
+        }
+        mgr.add(Task::new(
+            "x".to_string(),
+            "".to_string(),
+            TaskPriority::Watch,
+        ))?;
+        let names = |mgr: &TaskManager, prio| -> Result<Vec<String>> {
+            Ok(mgr.bucket(&prio)?.into_iter().map(|t| t.short).collect())
+        };
+
+        mgr.move_task(3, Placement::Top)?;
+        assert_eq!(names(&mgr, TaskPriority::ToDo)?, vec!["c", "a", "b"]);
+        mgr.move_task(3, Placement::After(2))?;
+        assert_eq!(names(&mgr, TaskPriority::ToDo)?, vec!["a", "b", "c"]);
+        mgr.move_task(1, Placement::Bottom)?;
+        assert_eq!(names(&mgr, TaskPriority::ToDo)?, vec!["b", "c", "a"]);
+
+        // new tasks come last until they are moved
+        mgr.add(Task::new(
+            "d".to_string(),
+            "".to_string(),
+            TaskPriority::ToDo,
+        ))?;
+        assert_eq!(names(&mgr, TaskPriority::ToDo)?, vec!["b", "c", "a", "d"]);
+
+        // moving next to a task of another priority takes over that priority
+        mgr.move_task(4, Placement::Before(3))?;
+        assert_eq!(
+            names(&mgr, TaskPriority::ToDo)?,
+            vec!["b", "x", "c", "a", "d"]
+        );
+        assert!(names(&mgr, TaskPriority::Watch)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn sort_keys() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let keys: Vec<SortKey> = ["status", "-prio", "name"]
+            .iter()
+            .map(|k| k.parse().unwrap())
+            .collect();
+        for (kind, file) in [
+            (Datasources::SqlLite, "t.sql"),
+            (Datasources::Json, "t.json"),
+        ] {
+            let path = dir.path().join(file).to_string_lossy().to_string();
+            let mut mgr = TaskManager::new(kind, &path)?;
+            for (name, prio) in [
+                ("b", TaskPriority::ToDo),
+                ("a", TaskPriority::ToDo),
+                ("urgent", TaskPriority::UrgentAndImportant),
+                ("later", TaskPriority::SomeDay),
+            ] {
+                mgr.add(Task::new(name.to_string(), "".to_string(), prio))?;
+            }
+            let mut task = mgr.get_task(3)?;
+            task.status = TaskStatus::Started;
+            mgr.set_status(3, task)?;
+
+            let rows = mgr.list_rows(&None, &keys, false)?;
+            let names: Vec<&str> = rows.iter().map(|r| r.task.short.as_str()).collect();
+            assert_eq!(names, vec!["urgent", "later", "a", "b"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn snooze() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        for name in ["now", "later"] {
+            mgr.add(Task::new(
+                name.to_string(),
+                "".to_string(),
+                TaskPriority::ToDo,
+            ))?;
+        }
+        mgr.snooze(1, Some(Utc::now() + chrono::Duration::days(3)))?;
+        let names = |mgr: &mut TaskManager| -> Result<Vec<String>> {
+            Ok(mgr
+                .list_rows(&None, &[], false)?
+                .into_iter()
+                .map(|r| r.task.short)
+                .collect())
+        };
+        assert_eq!(names(&mut mgr)?, vec!["later"]);
+        assert_eq!(mgr.next(2)?.len(), 1);
+
+        // comes back once the date has passed
+        mgr.snooze(1, Some(Utc::now() - chrono::Duration::hours(1)))?;
+        assert_eq!(names(&mut mgr)?, vec!["now", "later"]);
+        Ok(())
+    }
+
+    #[test]
+    fn recurring() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        let mut report = Task::new(
+            "report".to_string(),
+            "weekly".to_string(),
+            TaskPriority::ToDo,
+        );
+        report.recurrence = Some("weekly on fri".parse().unwrap());
+        mgr.add(report.clone())?;
+
+        mgr.done(1, false)?;
+        let done = mgr.get_task(1)?;
+        assert_eq!(done.recurrence, None);
+        let next = mgr.get_task(2)?;
+        assert_eq!(
+            (next.short.as_str(), next.desc.as_str()),
+            ("report", "weekly")
+        );
+        assert_eq!(next.series, Some(report.uuid));
+        assert!(next.is_snoozed(Utc::now()));
+        assert_eq!(mgr.recurring()?.len(), 1);
+
+        // completing it again doesn't create another one
+        mgr.set_status(1, done)?;
+        mgr.stop_series(2)?;
+        mgr.done(2, false)?;
+        assert!(mgr.get_task(3).is_err());
+        assert!(mgr.recurring()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn bulk_updates() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        for (name, prio) in [
+            ("release-1", TaskPriority::ToDo),
+            ("idea", TaskPriority::Watch),
+            ("other idea", TaskPriority::Watch),
+        ] {
+            mgr.add(Task::new(name.to_string(), "".to_string(), prio))?;
+        }
+        let mut notes = Task::new("notes".to_string(), "".to_string(), TaskPriority::Watch);
+        notes.parent = Some(mgr.get_task(1)?.uuid);
+        notes.recurrence = Some("weekly".parse().unwrap());
+        mgr.add(notes)?;
+
+        let watch = mgr.select(&"prio:Watch name:idea".parse().unwrap())?;
+        assert_eq!(watch.len(), 2);
+        assert_eq!(mgr.set_priority_all(&watch, TaskPriority::SomeDay)?, 2);
+        assert_eq!(mgr.get_task(3)?.prio, TaskPriority::SomeDay);
+        assert_eq!(mgr.get_task(4)?.prio, TaskPriority::Watch);
+
+        // completing the group spawns the next instance of the recurring subtask as well
+        let release = mgr.select(&"group:release-1".parse().unwrap())?;
+        assert_eq!(release.len(), 2);
+        assert_eq!(mgr.set_status_all(&release, TaskStatus::Completed)?, 2);
+        assert!(mgr.get_task(4)?.completed.is_some());
+        assert_eq!(mgr.get_task(5)?.short, "notes");
+        Ok(())
+    }
+
+    #[test]
+    fn time_tracking() -> Result<()> {
+        let f = tempfile::NamedTempFile::new()?;
+        let mut mgr = TaskManager::new(Datasources::SqlLite, &f.path().to_string_lossy())?;
+        for name in ["code", "review"] {
+            mgr.add(Task::new(
+                name.to_string(),
+                "".to_string(),
+                TaskPriority::ToDo,
+            ))?;
+        }
+        mgr.start_work(1)?;
+        assert_eq!(mgr.get_task(1)?.status, TaskStatus::Started);
+        assert!(mgr.get_task(1)?.started.is_some());
+
+        // starting another task stops the first one
+        mgr.start_work(2)?;
+        assert_eq!(mgr.active_work()?.unwrap().0.short, "review");
+        assert_eq!(mgr.source.sessions()?.len(), 2);
+        assert!(mgr.stop_work()?.is_some());
+        assert!(mgr.stop_work()?.is_none());
+
+        let sheet = mgr.timesheet(Utc::now() - Duration::days(1), Utc::now())?;
+        assert!(sheet.total < Duration::minutes(1));
+        Ok(())
+    }
+
+    /// A git repository in `dir` with a committer, for the git wrapped datasource.
+    fn init_git(dir: &std::path::Path) {
+        for args in [
+            vec!["init", "-q"],
+            vec!["config", "user.name", "myprio"],
+            vec!["config", "user.email", "myprio@localhost"],
+        ] {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .status()
+                .expect("git should be installed");
+            assert!(status.success());
+        }
+    }
+
+    fn git_commits(dir: &std::path::Path) -> String {
+        let out = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["rev-list", "--count", "HEAD"])
+            .output()
+            .expect("git should be installed");
+        String::from_utf8_lossy(&out.stdout).to_string()
+    }
+
+    #[test]
+    fn failed_updates_roll_back() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        init_git(dir.path());
+        for (kind, file, git) in [
+            (Datasources::SqlLite, "tasks.sql", false),
+            (Datasources::Json, "tasks.json", false),
+            (Datasources::SqlLite, "versioned.sql", true),
+        ] {
+            let path = dir.path().join(file).to_string_lossy().to_string();
+            let mut mgr = TaskManager::builder(kind, &path).git(git).build()?;
+            for name in ["one", "two"] {
+                mgr.add(Task::new(
+                    name.to_string(),
+                    "".to_string(),
+                    TaskPriority::ToDo,
+                ))?;
+            }
+            let commits = git_commits(dir.path());
+
+            // the third task is gone by the time it is updated, after the first two were
+            let mut tasks = vec![mgr.get_task(1)?, mgr.get_task(2)?];
+            tasks.push(Task {
+                id: Some(99),
+                ..tasks[0].clone()
+            });
+            assert!(matches!(
+                mgr.set_status_all(&tasks, TaskStatus::Completed),
+                Err(Error::NotFound { .. })
+            ));
+            drop(mgr);
+
+            let mut mgr = TaskManager::builder(kind, &path).git(git).build()?;
+            for id in [1, 2] {
+                assert_eq!(mgr.get_task(id)?.status, TaskStatus::Created, "{}", file);
+            }
+            assert_eq!(git_commits(dir.path()), commits, "{}", file);
+
+            // nothing to end without a transaction
+            assert!(matches!(
+                mgr.source.commit_transaction(),
+                Err(Error::Storage { .. })
+            ));
+            assert!(matches!(
+                mgr.source.rollback_transaction(),
+                Err(Error::Storage { .. })
+            ));
+            mgr.transaction(|mgr| mgr.source.update_task(1, mgr.source.get(1)?))?;
+        }
+        Ok(())
+    }
 }
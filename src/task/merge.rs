@@ -0,0 +1,705 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::task::deps::Dependency;
+use crate::task::time::Session;
+use crate::task::{Task, Timestamp};
+use uuid::Uuid;
+
+/// Which side wins when both sides changed the same field of the same task.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum MergePolicy {
+    /// keep our local value
+    Ours,
+    /// take the value from the remote
+    #[default]
+    Theirs,
+    /// take the value of the task which was modified last
+    Newest,
+}
+
+impl MergePolicy {
+    /// Decides for `Ours` or `Theirs` given the two versions of a task.
+    pub fn side(&self, ours: &Task, theirs: &Task) -> MergePolicy {
+        match self {
+            Self::Newest if ours.modified > theirs.modified => Self::Ours,
+            Self::Newest => Self::Theirs,
+            side => *side,
+        }
+    }
+}
+
+/// A task which was edited on both sides and had to be resolved by the `MergePolicy`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub id: u64,
+    pub fields: Vec<&'static str>,
+    pub resolution: String,
+}
+
+/// The changes which have to be applied to our side to complete a merge.
+#[derive(Debug, Default)]
+pub struct Merge {
+    pub add: Vec<Task>,
+    pub update: Vec<Task>,
+    pub remove: Vec<u64>,
+    pub conflicts: Vec<Conflict>,
+    pub add_dependencies: Vec<Dependency>,
+    pub remove_dependencies: Vec<Dependency>,
+    pub add_sessions: Vec<Session>,
+}
+
+/// Summary of a sync run, printed to the user once it is done.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    /// tasks added or updated on the other side, if it is written to
+    pub pushed: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Display for SyncReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "added: {}, updated: {}, removed: {}, conflicts: {}",
+            self.added,
+            self.updated,
+            self.removed,
+            self.conflicts.len()
+        )?;
+        if self.pushed > 0 {
+            writeln!(f, "pushed: {}", self.pushed)?;
+        }
+        for c in &self.conflicts {
+            writeln!(
+                f,
+                "  task {}: {} ({})",
+                c.id,
+                c.resolution,
+                c.fields.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Three-way merge of task lists, keyed by task uuid.
+///
+/// Fields changed on only one side are taken from that side, fields changed on both sides
+/// are resolved by `policy`. A task removed on one side but edited on the other is kept.
+/// Both are reported as conflicts.
+pub fn merge(base: &[Task], ours: &[Task], theirs: &[Task], policy: MergePolicy) -> Merge {
+    let base = by_uuid(base);
+    let ours = by_uuid(ours);
+    let theirs = by_uuid(theirs);
+    let mut result = Merge::default();
+
+    let mut uuids: Vec<Uuid> = base.keys().chain(theirs.keys()).copied().collect();
+    uuids.sort();
+    uuids.dedup();
+
+    for uuid in uuids {
+        match (base.get(&uuid), ours.get(&uuid), theirs.get(&uuid)) {
+            (Some(b), Some(o), Some(t)) => {
+                if same_content(t, b) || same_content(t, o) {
+                    continue;
+                }
+                if same_content(o, b) {
+                    result.update.push(Task {
+                        id: o.id,
+                        ..(*t).clone()
+                    });
+                    continue;
+                }
+                let mut fields = vec![];
+                let merged = merge_task(b, o, t, policy.side(o, t), &mut fields);
+                if !fields.is_empty() {
+                    result.conflicts.push(Conflict {
+                        id: o.id.unwrap_or_default(),
+                        fields,
+                        resolution: resolution(policy.side(o, t)).to_string(),
+                    });
+                }
+                if &merged != *o {
+                    result.update.push(merged);
+                }
+            }
+            // added on both sides, e.g. synced from the same third database
+            (None, Some(o), Some(t)) if !same_content(o, t) => {
+                let side = policy.side(o, t);
+                result.conflicts.push(Conflict {
+                    id: o.id.unwrap_or_default(),
+                    fields: differing_fields(o, t),
+                    resolution: resolution(side).to_string(),
+                });
+                if side == MergePolicy::Theirs {
+                    result.update.push(Task {
+                        id: o.id,
+                        ..(*t).clone()
+                    });
+                }
+            }
+            // removed by them
+            (Some(b), Some(o), None) => {
+                if same_content(o, b) {
+                    result.remove.push(o.id.unwrap_or_default());
+                } else {
+                    result.conflicts.push(Conflict {
+                        id: o.id.unwrap_or_default(),
+                        fields: vec![],
+                        resolution: "removed by them but edited by us, kept".to_string(),
+                    });
+                }
+            }
+            // removed by us
+            (Some(b), None, Some(t)) if !same_content(t, b) => {
+                result.add.push(Task {
+                    id: None,
+                    ..(*t).clone()
+                });
+                result.conflicts.push(Conflict {
+                    id: t.id.unwrap_or_default(),
+                    fields: vec![],
+                    resolution: "removed by us but edited by them, restored".to_string(),
+                });
+            }
+            (None, None, Some(t)) => result.add.push(Task {
+                id: None,
+                ..(*t).clone()
+            }),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Three-way merge of the dependencies between tasks: whatever they added or removed since
+/// `base` is added or removed on our side as well.
+pub fn merge_dependencies(
+    base: &[Dependency],
+    ours: &[Dependency],
+    theirs: &[Dependency],
+    merge: &mut Merge,
+) {
+    merge.add_dependencies = theirs
+        .iter()
+        .filter(|d| !base.contains(d) && !ours.contains(d))
+        .copied()
+        .collect();
+    merge.remove_dependencies = base
+        .iter()
+        .filter(|d| !theirs.contains(d) && ours.contains(d))
+        .copied()
+        .collect();
+}
+
+/// Work sessions of `theirs` which `ours` is missing, or which ended on their side while
+/// they are still running on ours. Sessions are only ever added, never removed.
+pub fn merge_sessions(ours: &[Session], theirs: &[Session]) -> Vec<Session> {
+    theirs
+        .iter()
+        .filter(
+            |t| match ours.iter().find(|o| o.task == t.task && o.start == t.start) {
+                Some(o) => o.end.is_none() && t.end.is_some(),
+                None => true,
+            },
+        )
+        .copied()
+        .collect()
+}
+
+fn resolution(side: MergePolicy) -> &'static str {
+    match side {
+        MergePolicy::Theirs => "took their value",
+        _ => "kept our value",
+    }
+}
+
+/// A removed task, kept by the datasource so a two-way sync doesn't bring it back.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub uuid: Uuid,
+    pub removed: Timestamp,
+}
+
+/// Changes needed to bring two task lists without a common history in sync, keyed by uuid.
+#[derive(Debug, Default)]
+pub struct Reconcile {
+    /// tasks to add to (without id) or update on our side
+    pub ours: Vec<Task>,
+    /// tasks to add to (without id) or update on their side
+    pub theirs: Vec<Task>,
+    /// tasks which differ on both sides, as (ours, theirs)
+    pub conflicts: Vec<(Task, Task)>,
+    /// ids of our tasks which they removed
+    pub remove_ours: Vec<u64>,
+    /// ids of their tasks which we removed
+    pub remove_theirs: Vec<u64>,
+    /// ids of our tasks which they removed but we edited since, copied back to them
+    pub kept: Vec<u64>,
+    /// uuids of their tasks which we removed but they edited since, copied back to us
+    pub restored: Vec<Uuid>,
+}
+
+/// Two-way comparison of task lists. Tasks missing on one side are copied over, unless the
+/// other side removed them before they were last modified. Tasks which differ are left to
+/// the caller to resolve, e.g. by `MergePolicy::side`.
+pub fn reconcile(
+    ours: &[Task],
+    theirs: &[Task],
+    our_tombstones: &[Tombstone],
+    their_tombstones: &[Tombstone],
+) -> Reconcile {
+    let mut result = Reconcile::default();
+    let theirs_by_uuid = by_uuid(theirs);
+    let ours_by_uuid = by_uuid(ours);
+    let removed = |tombstones: &[Tombstone], task: &Task| {
+        tombstones
+            .iter()
+            .find(|r| r.uuid == task.uuid)
+            .map(|r| r.removed >= task.modified)
+    };
+
+    for o in ours {
+        match theirs_by_uuid.get(&o.uuid) {
+            None => match removed(their_tombstones, o) {
+                Some(true) => result.remove_ours.push(o.id.unwrap_or_default()),
+                edited => {
+                    if edited.is_some() {
+                        result.kept.push(o.id.unwrap_or_default());
+                    }
+                    result.theirs.push(Task {
+                        id: None,
+                        ..o.clone()
+                    });
+                }
+            },
+            Some(t) if !same_content(o, t) => result.conflicts.push((o.clone(), (*t).clone())),
+            Some(_) => {}
+        }
+    }
+    for t in theirs {
+        if ours_by_uuid.contains_key(&t.uuid) {
+            continue;
+        }
+        match removed(our_tombstones, t) {
+            Some(true) => result.remove_theirs.push(t.id.unwrap_or_default()),
+            edited => {
+                if edited.is_some() {
+                    result.restored.push(t.uuid);
+                }
+                result.ours.push(Task {
+                    id: None,
+                    ..t.clone()
+                });
+            }
+        }
+    }
+    result
+}
+
+/// Equality of everything but the local id.
+pub fn same_content(a: &Task, b: &Task) -> bool {
+    Task {
+        id: a.id,
+        ..b.clone()
+    } == *a
+}
+
+/// Names of the fields in which two versions of a task differ, all but the local id.
+pub fn differing_fields(a: &Task, b: &Task) -> Vec<&'static str> {
+    let mut fields = vec![];
+    if a.uuid != b.uuid {
+        fields.push("uuid");
+    }
+    if a.short != b.short {
+        fields.push("short");
+    }
+    if a.desc != b.desc {
+        fields.push("desc");
+    }
+    if a.created != b.created {
+        fields.push("created");
+    }
+    if a.started != b.started {
+        fields.push("started");
+    }
+    if a.status != b.status {
+        fields.push("status");
+    }
+    if a.prio != b.prio {
+        fields.push("prio");
+    }
+    if a.parent != b.parent {
+        fields.push("parent");
+    }
+    if a.rank != b.rank {
+        fields.push("rank");
+    }
+    if a.snoozed_until != b.snoozed_until {
+        fields.push("snoozed_until");
+    }
+    if a.recurrence != b.recurrence {
+        fields.push("recurrence");
+    }
+    if a.series != b.series {
+        fields.push("series");
+    }
+    if a.due != b.due {
+        fields.push("due");
+    }
+    if a.estimate != b.estimate {
+        fields.push("estimate");
+    }
+    if a.completed != b.completed {
+        fields.push("completed");
+    }
+    if a.modified != b.modified {
+        fields.push("modified");
+    }
+    fields
+}
+
+/// The value of `field` of a task as shown to the user, for the names `differing_fields` returns.
+pub fn field_value(task: &Task, field: &str) -> String {
+    fn show<T: Display>(value: &Option<T>) -> String {
+        value.as_ref().map_or("none".to_string(), |v| v.to_string())
+    }
+    let time = |t: &Option<Timestamp>| show(&t.map(|t| t.format("%Y-%m-%d %H:%M")));
+    match field {
+        "uuid" => task.uuid.to_string(),
+        "short" => task.short.clone(),
+        "desc" => task.desc.clone(),
+        "created" => time(&Some(task.created)),
+        "started" => time(&task.started),
+        "status" => task.status.to_string(),
+        "prio" => task.prio.to_string(),
+        "parent" => show(&task.parent),
+        "rank" => show(&task.rank),
+        "snoozed_until" => time(&task.snoozed_until),
+        "recurrence" => show(&task.recurrence),
+        "series" => show(&task.series),
+        "due" => time(&task.due),
+        "estimate" => show(&task.estimate),
+        "completed" => time(&task.completed),
+        "modified" => time(&Some(task.modified)),
+        _ => String::new(),
+    }
+}
+
+fn by_uuid(tasks: &[Task]) -> BTreeMap<Uuid, &Task> {
+    tasks.iter().map(|t| (t.uuid, t)).collect()
+}
+
+fn merge_task(
+    base: &Task,
+    ours: &Task,
+    theirs: &Task,
+    policy: MergePolicy,
+    conflicts: &mut Vec<&'static str>,
+) -> Task {
+    Task {
+        id: ours.id,
+        uuid: ours.uuid,
+        modified: ours.modified.max(theirs.modified),
+        short: pick(
+            "short",
+            &base.short,
+            &ours.short,
+            &theirs.short,
+            policy,
+            conflicts,
+        ),
+        desc: pick(
+            "desc",
+            &base.desc,
+            &ours.desc,
+            &theirs.desc,
+            policy,
+            conflicts,
+        ),
+        created: pick(
+            "created",
+            &base.created,
+            &ours.created,
+            &theirs.created,
+            policy,
+            conflicts,
+        ),
+        started: pick(
+            "started",
+            &base.started,
+            &ours.started,
+            &theirs.started,
+            policy,
+            conflicts,
+        ),
+        status: pick(
+            "status",
+            &base.status,
+            &ours.status,
+            &theirs.status,
+            policy,
+            conflicts,
+        ),
+        parent: pick(
+            "parent",
+            &base.parent,
+            &ours.parent,
+            &theirs.parent,
+            policy,
+            conflicts,
+        ),
+        rank: pick(
+            "rank",
+            &base.rank,
+            &ours.rank,
+            &theirs.rank,
+            policy,
+            conflicts,
+        ),
+        snoozed_until: pick(
+            "snoozed_until",
+            &base.snoozed_until,
+            &ours.snoozed_until,
+            &theirs.snoozed_until,
+            policy,
+            conflicts,
+        ),
+        recurrence: pick(
+            "recurrence",
+            &base.recurrence,
+            &ours.recurrence,
+            &theirs.recurrence,
+            policy,
+            conflicts,
+        ),
+        series: pick(
+            "series",
+            &base.series,
+            &ours.series,
+            &theirs.series,
+            policy,
+            conflicts,
+        ),
+        due: pick("due", &base.due, &ours.due, &theirs.due, policy, conflicts),
+        estimate: pick(
+            "estimate",
+            &base.estimate,
+            &ours.estimate,
+            &theirs.estimate,
+            policy,
+            conflicts,
+        ),
+        completed: pick(
+            "completed",
+            &base.completed,
+            &ours.completed,
+            &theirs.completed,
+            policy,
+            conflicts,
+        ),
+        prio: pick(
+            "prio",
+            &base.prio,
+            &ours.prio,
+            &theirs.prio,
+            policy,
+            conflicts,
+        ),
+    }
+}
+
+fn pick<T: PartialEq + Clone>(
+    field: &'static str,
+    base: &T,
+    ours: &T,
+    theirs: &T,
+    policy: MergePolicy,
+    conflicts: &mut Vec<&'static str>,
+) -> T {
+    if theirs == base || theirs == ours {
+        return ours.clone();
+    }
+    if ours == base {
+        return theirs.clone();
+    }
+    conflicts.push(field);
+    match policy {
+        MergePolicy::Ours => ours.clone(),
+        _ => theirs.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{TaskPriority, TaskStatus};
+    use chrono::Duration;
+    use std::slice;
+
+    fn task(id: u64, name: &str) -> Task {
+        Task {
+            id: Some(id),
+            ..Task::new(name.to_string(), "".to_string(), TaskPriority::ToDo)
+        }
+    }
+
+    /// `task` changed by `f`, `minutes` later.
+    fn edited(task: &Task, minutes: i64, f: impl FnOnce(&mut Task)) -> Task {
+        let mut t = task.clone();
+        f(&mut t);
+        t.modified = task.modified + Duration::minutes(minutes);
+        t
+    }
+
+    fn merge_one(base: &Task, ours: &Task, theirs: &Task, policy: MergePolicy) -> Merge {
+        merge(
+            slice::from_ref(base),
+            slice::from_ref(ours),
+            slice::from_ref(theirs),
+            policy,
+        )
+    }
+
+    #[test]
+    fn one_side_edits() {
+        let base = task(1, "report");
+        let theirs = edited(&base, 1, |t| t.status = TaskStatus::Started);
+        let result = merge_one(&base, &base, &theirs, MergePolicy::Ours);
+        assert_eq!(result.update, vec![theirs]);
+        assert!(result.conflicts.is_empty());
+
+        let ours = edited(&base, 1, |t| t.prio = TaskPriority::Watch);
+        let result = merge_one(&base, &ours, &base, MergePolicy::Theirs);
+        assert!(result.update.is_empty() && result.conflicts.is_empty());
+    }
+
+    #[test]
+    fn both_sides_edit() {
+        let base = task(1, "report");
+        let ours = edited(&base, 1, |t| {
+            t.status = TaskStatus::WontDo;
+            t.desc = "weekly".to_string();
+        });
+        let theirs = edited(&base, 2, |t| {
+            t.status = TaskStatus::Completed;
+            t.prio = TaskPriority::Watch;
+        });
+
+        for (policy, status) in [
+            (MergePolicy::Ours, TaskStatus::WontDo),
+            (MergePolicy::Theirs, TaskStatus::Completed),
+            (MergePolicy::Newest, TaskStatus::Completed),
+        ] {
+            let result = merge_one(&base, &ours, &theirs, policy);
+            assert_eq!(result.conflicts.len(), 1, "{:?}", policy);
+            assert_eq!(result.conflicts[0].fields, vec!["status"], "{:?}", policy);
+            // fields changed on one side only are merged whatever the policy
+            let merged = &result.update[0];
+            assert_eq!(merged.status, status, "{:?}", policy);
+            assert_eq!(merged.desc, "weekly");
+            assert_eq!(merged.prio, TaskPriority::Watch);
+            assert_eq!(merged.modified, theirs.modified);
+        }
+        // the older side loses with Newest
+        let result = merge_one(&base, &theirs, &ours, MergePolicy::Newest);
+        assert_eq!(result.update[0].status, TaskStatus::Completed);
+    }
+
+    #[test]
+    fn removed_on_one_side() {
+        let base = task(1, "report");
+        let changed = edited(&base, 1, |t| t.prio = TaskPriority::Watch);
+
+        let result = merge(
+            slice::from_ref(&base),
+            slice::from_ref(&changed),
+            &[],
+            MergePolicy::Theirs,
+        );
+        assert!(result.remove.is_empty());
+        assert_eq!(
+            result.conflicts[0].resolution,
+            "removed by them but edited by us, kept"
+        );
+        let result = merge(
+            slice::from_ref(&base),
+            slice::from_ref(&base),
+            &[],
+            MergePolicy::Theirs,
+        );
+        assert_eq!(result.remove, vec![1]);
+
+        let result = merge(slice::from_ref(&base), &[], &[changed], MergePolicy::Ours);
+        assert_eq!(result.add.len(), 1);
+        assert_eq!(result.add[0].id, None);
+        assert_eq!(
+            result.conflicts[0].resolution,
+            "removed by us but edited by them, restored"
+        );
+        let result = merge(
+            slice::from_ref(&base),
+            &[],
+            slice::from_ref(&base),
+            MergePolicy::Ours,
+        );
+        assert!(result.add.is_empty() && result.conflicts.is_empty());
+    }
+
+    #[test]
+    fn reconcile_with_tombstones() {
+        let ours = task(1, "report");
+        let theirs = Task {
+            id: Some(7),
+            ..ours.clone()
+        };
+        assert!(same_content(&ours, &theirs));
+        let removed = |t: &Task, minutes: i64| Tombstone {
+            uuid: t.uuid,
+            removed: t.modified + Duration::minutes(minutes),
+        };
+
+        // without a tombstone a missing task is copied over
+        let result = reconcile(slice::from_ref(&ours), &[], &[], &[]);
+        assert_eq!(result.theirs.len(), 1);
+        assert_eq!(result.theirs[0].id, None);
+
+        // they removed it after our last edit, so it goes here too
+        let result = reconcile(slice::from_ref(&ours), &[], &[], &[removed(&ours, 1)]);
+        assert_eq!(result.remove_ours, vec![1]);
+        assert!(result.theirs.is_empty() && result.kept.is_empty());
+        // we edited it after they removed it, so it goes back to them
+        let result = reconcile(slice::from_ref(&ours), &[], &[], &[removed(&ours, -1)]);
+        assert_eq!(result.kept, vec![1]);
+        assert_eq!(result.theirs.len(), 1);
+
+        let result = reconcile(&[], slice::from_ref(&theirs), &[removed(&theirs, 1)], &[]);
+        assert_eq!(result.remove_theirs, vec![7]);
+        assert!(result.ours.is_empty() && result.restored.is_empty());
+        let result = reconcile(&[], slice::from_ref(&theirs), &[removed(&theirs, -1)], &[]);
+        assert_eq!(result.restored, vec![theirs.uuid]);
+        assert_eq!(result.ours.len(), 1);
+
+        // a tombstone doesn't matter while both sides have the task
+        let changed = edited(&theirs, 1, |t| t.status = TaskStatus::Started);
+        let result = reconcile(
+            slice::from_ref(&ours),
+            slice::from_ref(&changed),
+            &[removed(&ours, 5)],
+            &[],
+        );
+        assert_eq!(result.conflicts, vec![(ours.clone(), changed.clone())]);
+        assert_eq!(
+            differing_fields(&ours, &changed),
+            vec!["status", "modified"]
+        );
+        assert_eq!(field_value(&ours, "status"), "Created");
+    }
+}
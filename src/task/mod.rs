@@ -1,43 +1,30 @@
+pub mod chart;
+pub mod date;
+pub mod deps;
+pub mod estimate;
+pub mod filter;
 pub mod manager;
+pub mod merge;
+pub mod next;
+pub mod recur;
+pub mod review;
+pub mod search;
+pub mod sort;
+pub mod stats;
+pub mod time;
 
 use std::cmp::Ordering;
+use std::fmt::Debug;
 use std::str::FromStr;
-use std::{
-    error::Error,
-    fmt::{Debug, Display},
-};
 
 use chrono::{DateTime, Utc};
+use estimate::Estimate;
+use recur::Recurrence;
+use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
+use uuid::Uuid;
 
-pub struct NoSuchStatusError {}
-impl Error for NoSuchStatusError {
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
-    }
-
-    fn description(&self) -> &str {
-        "description() is deprecated; use Display"
-    }
-
-    fn cause(&self) -> Option<&dyn Error> {
-        self.source()
-    }
-}
-
-impl Display for NoSuchStatusError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Status does not exist")
-    }
-}
-
-impl Debug for NoSuchStatusError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("NoSuchStatusError").finish()
-    }
-}
-
-#[derive(Debug, EnumIter, Clone)]
+#[derive(Debug, PartialEq, Eq, EnumIter, Clone, Serialize, Deserialize)]
 pub enum TaskStatus {
     Created,
     Started,
@@ -47,7 +34,7 @@ pub enum TaskStatus {
     Deleted,
 }
 
-#[derive(Debug, PartialEq, Eq, EnumIter, Clone)]
+#[derive(Debug, PartialEq, Eq, EnumIter, Clone, Serialize, Deserialize)]
 pub enum TaskPriority {
     UrgentAndImportant,
     UrgentNotImportant,
@@ -60,31 +47,108 @@ pub enum TaskPriority {
 
 pub type Timestamp = DateTime<Utc>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub id: Option<u64>,
+    /// Identifies the task across databases, unlike `id` which is only valid locally
+    #[serde(default = "Uuid::new_v4")]
+    pub uuid: Uuid,
     pub short: String,
     pub desc: String,
     pub created: Timestamp,
     pub started: Option<Timestamp>,
     pub status: TaskStatus,
     pub prio: TaskPriority,
+    #[serde(default = "Utc::now")]
+    pub modified: Timestamp,
+    /// uuid of the task this is a subtask of
+    #[serde(default)]
+    pub parent: Option<Uuid>,
+    /// Manual position within the priority, lower comes first. Unranked tasks come last
+    #[serde(default)]
+    pub rank: Option<u32>,
+    /// Hidden from `list` and `next` until then
+    #[serde(default)]
+    pub snoozed_until: Option<Timestamp>,
+    /// Completing the task creates the next instance of the series
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// uuid of the first task of the series a recurring task belongs to
+    #[serde(default)]
+    pub series: Option<Uuid>,
+    /// When a recurring task is due, the next task of the series is counted from it
+    #[serde(default)]
+    pub due: Option<Timestamp>,
+    #[serde(default)]
+    pub estimate: Option<Estimate>,
+    /// When the task was last completed
+    #[serde(default)]
+    pub completed: Option<Timestamp>,
 }
 
 impl Task {
     pub fn new(short: String, desc: String, prio: TaskPriority) -> Self {
+        let now = Utc::now();
         Self {
             short,
             desc,
-            created: Utc::now(),
+            created: now,
             id: None,
+            uuid: Uuid::new_v4(),
             started: None,
             status: TaskStatus::Created,
-            prio: prio,
+            prio,
+            modified: now,
+            parent: None,
+            rank: None,
+            snoozed_until: None,
+            recurrence: None,
+            series: None,
+            due: None,
+            estimate: None,
+            completed: None,
         }
     }
 }
 
+impl Task {
+    /// Whether the task is snoozed at `now`.
+    pub fn is_snoozed(&self, now: Timestamp) -> bool {
+        self.snoozed_until.is_some_and(|until| until > now)
+    }
+}
+
+impl TaskStatus {
+    /// Whether there is still something to be done for a task.
+    pub fn is_open(&self) -> bool {
+        matches!(self, Self::Created | Self::Started)
+    }
+
+    /// Tasks being worked on come first, then the ones still to do, then the closed ones.
+    pub(crate) fn get_ordering_value(&self) -> u8 {
+        match self {
+            Self::Started => 1,
+            Self::Created => 2,
+            Self::Completed => 3,
+            Self::WontDo => 4,
+            Self::Archived => 5,
+            Self::Deleted => 6,
+        }
+    }
+}
+
+impl PartialOrd for TaskStatus {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TaskStatus {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get_ordering_value().cmp(&other.get_ordering_value())
+    }
+}
+
 // TODO
 #[allow(clippy::to_string_trait_impl)]
 impl ToString for TaskStatus {
@@ -101,7 +165,7 @@ impl ToString for TaskStatus {
 }
 
 impl FromStr for TaskStatus {
-    type Err = NoSuchStatusError;
+    type Err = crate::error::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
@@ -111,13 +175,13 @@ impl FromStr for TaskStatus {
             "WontDo" => Ok(Self::WontDo),
             "Archived" => Ok(Self::Archived),
             "Deleted" => Ok(Self::Deleted),
-            _ => Err(NoSuchStatusError {}),
+            _ => Err(Self::Err::InvalidStatus(s.to_string())),
         }
     }
 }
 
 impl TaskPriority {
-    fn get_ordering_value(&self) -> u8 {
+    pub(crate) fn get_ordering_value(&self) -> u8 {
         match self {
             Self::UrgentAndImportant => 1,
             Self::UrgentNotImportant => 2,
@@ -132,8 +196,7 @@ impl TaskPriority {
 
 impl PartialOrd for TaskPriority {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.get_ordering_value()
-            .partial_cmp(&other.get_ordering_value())
+        Some(self.cmp(other))
     }
 }
 
@@ -160,7 +223,7 @@ impl ToString for TaskPriority {
 }
 
 impl FromStr for TaskPriority {
-    type Err = NoSuchStatusError;
+    type Err = crate::error::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
@@ -171,8 +234,7 @@ impl FromStr for TaskPriority {
             "Watch" => Ok(Self::Watch),
             "Some Day" => Ok(Self::SomeDay),
             "Nice To Do" => Ok(Self::NiceToDo),
-            //TODO
-            _ => Err(NoSuchStatusError {}),
+            _ => Err(Self::Err::InvalidPriority(s.to_string())),
         }
     }
 }
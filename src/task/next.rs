@@ -0,0 +1,133 @@
+use std::cmp::Reverse;
+
+use crate::task::deps::{self, Dependency};
+use crate::task::{Task, TaskPriority, TaskStatus, Timestamp};
+
+/// A task worth working on next, with the reasons it was picked.
+#[derive(Debug)]
+pub struct Suggestion {
+    pub task: Task,
+    pub reasons: Vec<String>,
+}
+
+/// Picks the `n` tasks to work on next out of `all`.
+///
+/// Only open tasks which are not blocked, not snoozed and have no open subtasks of their
+/// own are considered. They are ranked by priority (including the priority of tasks they block),
+/// started before created, their manual rank within the priority and then the oldest first.
+pub fn suggest(all: &[Task], deps: &[Dependency], n: usize, now: Timestamp) -> Vec<Suggestion> {
+    let blocked = deps::blocked(all, deps);
+    let mut candidates: Vec<(TaskPriority, &Task)> = all
+        .iter()
+        .filter(|t| t.status.is_open() && !blocked.contains(&t.uuid) && !t.is_snoozed(now))
+        .filter(|t| {
+            !all.iter()
+                .any(|c| c.parent == Some(t.uuid) && c.status.is_open())
+        })
+        .map(|t| (deps::effective_priority(t, all, deps), t))
+        .collect();
+    candidates.sort_by_key(|(prio, t)| {
+        (
+            prio.clone(),
+            t.status != TaskStatus::Started,
+            t.rank.is_none(),
+            t.rank,
+            Reverse(now - t.created),
+        )
+    });
+
+    candidates
+        .into_iter()
+        .take(n)
+        .map(|(prio, t)| {
+            let mut reasons = vec![t.prio.to_string()];
+            if prio != t.prio {
+                let waiting: Vec<String> = deps
+                    .iter()
+                    .filter(|d| d.blocker == t.uuid)
+                    .filter_map(|d| all.iter().find(|w| w.uuid == d.task))
+                    .filter(|w| w.status.is_open())
+                    .map(|w| format!("#{} {}", w.id.unwrap_or_default(), w.short))
+                    .collect();
+                reasons.push(format!(
+                    "blocks {} which is {}",
+                    waiting.join(", "),
+                    prio.to_string()
+                ));
+            }
+            if t.status == TaskStatus::Started {
+                reasons.push("already started".to_string());
+            }
+            if let Some(rank) = t.rank {
+                reasons.push(format!("ranked {} within its priority", rank));
+            }
+            reasons.push(format!("open for {} days", (now - t.created).num_days()));
+            Suggestion {
+                task: t.clone(),
+                reasons,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn ranking() {
+        let now = Utc::now();
+        let mut tasks = vec![];
+        for (id, name, prio, status, age) in [
+            (1, "old", TaskPriority::ToDo, TaskStatus::Created, 30),
+            (2, "new", TaskPriority::ToDo, TaskStatus::Created, 1),
+            (3, "started", TaskPriority::ToDo, TaskStatus::Started, 2),
+            (
+                4,
+                "urgent",
+                TaskPriority::UrgentAndImportant,
+                TaskStatus::Created,
+                0,
+            ),
+            (
+                5,
+                "done",
+                TaskPriority::UrgentAndImportant,
+                TaskStatus::Completed,
+                9,
+            ),
+            (6, "blocker", TaskPriority::SomeDay, TaskStatus::Created, 5),
+            (
+                7,
+                "blocked",
+                TaskPriority::ImportantNotUrgent,
+                TaskStatus::Created,
+                5,
+            ),
+        ] {
+            let mut t = Task::new(name.to_string(), "".to_string(), prio);
+            t.id = Some(id);
+            t.status = status;
+            t.created = now - Duration::days(age);
+            tasks.push(t);
+        }
+        let deps = vec![Dependency {
+            task: tasks[6].uuid,
+            blocker: tasks[5].uuid,
+        }];
+
+        let next = suggest(&tasks, &deps, 4, now);
+        let names: Vec<&str> = next.iter().map(|s| s.task.short.as_str()).collect();
+        assert_eq!(names, vec!["urgent", "blocker", "started", "old"]);
+        assert_eq!(
+            next[1].reasons,
+            vec![
+                "Some Day",
+                "blocks #7 blocked which is Important Not Urgent",
+                "open for 5 days"
+            ]
+        );
+        assert_eq!(next[2].reasons[1], "already started");
+    }
+}
@@ -0,0 +1,250 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, Local, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::task::{date, Task, Timestamp};
+
+/// How often a recurring task comes back, e.g. `weekly`, `every 2w on fri` or
+/// `monthly on 1`. Stored and shown in that form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Recurrence {
+    pub every: u32,
+    pub unit: Unit,
+    /// weekday (0 = monday) for weeks, day of month for months
+    pub on: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Day,
+    Week,
+    Month,
+}
+
+impl Recurrence {
+    /// The first date after `today` the task is due again, counting from `base`, the day
+    /// the previous instance was due (or completed if it had no date).
+    pub fn next_after(&self, base: NaiveDate, today: NaiveDate) -> NaiveDate {
+        let mut date = self.align(base);
+        while date <= today {
+            date = self.align(self.step(date));
+        }
+        date
+    }
+
+    fn step(&self, date: NaiveDate) -> NaiveDate {
+        match self.unit {
+            Unit::Day => date + Duration::days(self.every as i64),
+            Unit::Week => date + Duration::weeks(self.every as i64),
+            Unit::Month => date
+                .checked_add_months(Months::new(self.every))
+                .unwrap_or(NaiveDate::MAX),
+        }
+    }
+
+    /// Moves `date` onto the day the rule asks for, if any.
+    fn align(&self, date: NaiveDate) -> NaiveDate {
+        match (self.unit, self.on) {
+            (Unit::Week, Some(weekday)) => {
+                let ahead = (7 + weekday as i64 - date.weekday().num_days_from_monday() as i64) % 7;
+                date + Duration::days(ahead)
+            }
+            (Unit::Month, Some(day)) => (1..=day)
+                .rev()
+                .find_map(|d| date.with_day(d))
+                .unwrap_or(date),
+            _ => date,
+        }
+    }
+}
+
+/// The next task of the series `task` belongs to, snoozed until it is due. The rule moves
+/// on to the new task, so None if `task` doesn't recur.
+pub fn next_instance(task: &Task, now: Timestamp) -> Option<Task> {
+    let rule = task.recurrence?;
+    let today = now.with_timezone(&Local).date_naive();
+    let base = task
+        .due
+        .map(|due| due.with_timezone(&Local).date_naive())
+        .unwrap_or(today);
+    let mut next = Task::new(task.short.clone(), task.desc.clone(), task.prio.clone());
+    next.parent = task.parent;
+    next.estimate = task.estimate;
+    next.recurrence = Some(rule);
+    next.series = Some(task.series.unwrap_or(task.uuid));
+    next.due = date::start_of_day(rule.next_after(base, today)).ok();
+    next.snoozed_until = next.due;
+    Some(next)
+}
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.every, self.unit) {
+            (1, Unit::Day) => write!(f, "daily")?,
+            (1, Unit::Week) => write!(f, "weekly")?,
+            (1, Unit::Month) => write!(f, "monthly")?,
+            (n, Unit::Day) => write!(f, "every {}d", n)?,
+            (n, Unit::Week) => write!(f, "every {}w", n)?,
+            (n, Unit::Month) => write!(f, "every {}m", n)?,
+        }
+        match (self.unit, self.on) {
+            (Unit::Week, Some(day)) => write!(f, " on {}", WEEKDAYS[day as usize]),
+            (_, Some(day)) => write!(f, " on {}", day),
+            (_, None) => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            Error::InvalidInput(format!(
+                "invalid recurrence {}, use e.g. daily, weekly on fri, every 2w or monthly on 1",
+                s
+            ))
+        };
+        let s = s.trim().to_lowercase();
+        let (rule, on) = match s.split_once(" on ") {
+            Some((rule, on)) => (rule.trim(), Some(on.trim())),
+            None => (s.as_str(), None),
+        };
+        let (every, unit) = match rule {
+            "daily" => (1, Unit::Day),
+            "weekly" => (1, Unit::Week),
+            "monthly" => (1, Unit::Month),
+            _ => {
+                let span = rule.strip_prefix("every ").ok_or_else(invalid)?;
+                let at = span.char_indices().last().map_or(0, |(i, _)| i);
+                let (n, unit) = span.split_at(at);
+                let unit = match unit {
+                    "d" => Unit::Day,
+                    "w" => Unit::Week,
+                    "m" => Unit::Month,
+                    _ => return Err(invalid()),
+                };
+                (n.trim().parse::<u32>().map_err(|_| invalid())?, unit)
+            }
+        };
+        if every == 0 {
+            return Err(invalid());
+        }
+        let on = match (unit, on) {
+            (_, None) => None,
+            (Unit::Week, Some(day)) => Some(
+                day.parse::<Weekday>()
+                    .map_err(|_| invalid())?
+                    .num_days_from_monday(),
+            ),
+            (Unit::Month, Some(day)) => match day.parse::<u32>() {
+                Ok(day @ 1..=31) => Some(day),
+                _ => return Err(invalid()),
+            },
+            (Unit::Day, Some(_)) => return Err(invalid()),
+        };
+        Ok(Self { every, unit, on })
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = Error;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(r: Recurrence) -> Self {
+        r.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+    use chrono::Utc;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn rules() {
+        for rule in [
+            "daily",
+            "every 3d",
+            "weekly on fri",
+            "every 2w",
+            "monthly on 31",
+        ] {
+            assert_eq!(rule.parse::<Recurrence>().unwrap().to_string(), rule);
+        }
+        assert!("hourly".parse::<Recurrence>().is_err());
+        assert!("every 0d".parse::<Recurrence>().is_err());
+        assert!("daily on mon".parse::<Recurrence>().is_err());
+        assert!(matches!(
+            "every 2é".parse::<Recurrence>(),
+            Err(Error::InvalidInput(_))
+        ));
+
+        // monday 2026-10-19
+        let today = date(2026, 10, 19);
+        let weekly: Recurrence = "weekly on fri".parse().unwrap();
+        assert_eq!(weekly.next_after(today, today), date(2026, 10, 23));
+        assert_eq!(
+            weekly.next_after(date(2026, 10, 23), date(2026, 10, 23)),
+            date(2026, 10, 30)
+        );
+
+        // done late: the next date after today, keeping the cadence
+        let every: Recurrence = "every 2w".parse().unwrap();
+        assert_eq!(
+            every.next_after(date(2026, 10, 1), today),
+            date(2026, 10, 29)
+        );
+
+        let monthly: Recurrence = "monthly on 31".parse().unwrap();
+        assert_eq!(
+            monthly.next_after(date(2026, 1, 31), date(2026, 1, 31)),
+            date(2026, 2, 28)
+        );
+        assert_eq!(
+            monthly.next_after(date(2026, 2, 28), date(2026, 2, 28)),
+            date(2026, 3, 31)
+        );
+
+        let mut report = Task::new("report".to_string(), "".to_string(), TaskPriority::ToDo);
+        assert!(next_instance(&report, Utc::now()).is_none());
+        report.recurrence = Some(weekly);
+        let next = next_instance(&report, Utc::now()).unwrap();
+        assert_eq!(next.series, Some(report.uuid));
+        assert_eq!(next.recurrence, Some(weekly));
+        assert!(next.is_snoozed(Utc::now()));
+        assert_eq!(next.due, next.snoozed_until);
+
+        // unsnoozing the task keeps the cadence
+        let due = next.due;
+        let next = Task {
+            snoozed_until: None,
+            ..next
+        };
+        let after = next_instance(&next, Utc::now()).unwrap();
+        assert_eq!(
+            after.due,
+            date::start_of_day(weekly.next_after(
+                due.unwrap().with_timezone(&Local).date_naive(),
+                Local::now().date_naive()
+            ))
+            .ok()
+        );
+    }
+}
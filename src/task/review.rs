@@ -0,0 +1,283 @@
+use std::cmp::Reverse;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::error::Error;
+use crate::task::{filter, Task, TaskPriority, TaskStatus, Timestamp};
+
+/// How long a task may sit untouched in a priority before it comes up for review,
+/// given as e.g. `some-day=90` (days).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Threshold {
+    pub prio: TaskPriority,
+    pub days: i64,
+}
+
+/// What to do with a task which came up for review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum Action {
+    /// Raise the priority by one step
+    Promote,
+    /// Lower the priority by one step
+    Demote,
+    Archive,
+    /// Close the task as won't do
+    Drop,
+    /// Leave it as it is, which restarts its clock
+    Keep,
+}
+
+/// A task which has been untouched for longer than its priority allows.
+#[derive(Debug)]
+pub struct Stale {
+    pub task: Task,
+    pub days: i64,
+    pub suggestion: Action,
+}
+
+pub fn default_thresholds() -> Vec<Threshold> {
+    [
+        (TaskPriority::UrgentAndImportant, 3),
+        (TaskPriority::UrgentNotImportant, 7),
+        (TaskPriority::ImportantNotUrgent, 30),
+        (TaskPriority::ToDo, 30),
+        (TaskPriority::Watch, 14),
+        (TaskPriority::NiceToDo, 60),
+        (TaskPriority::SomeDay, 90),
+    ]
+    .into_iter()
+    .map(|(prio, days)| Threshold { prio, days })
+    .collect()
+}
+
+/// Open tasks untouched for longer than the threshold of their priority, the most overdue
+/// first. Age counts from the last change, or from creation if `since_created` is set.
+/// Priorities without a threshold in `thresholds` use the default one.
+pub fn stale(
+    all: &[Task],
+    thresholds: &[Threshold],
+    since_created: bool,
+    now: Timestamp,
+) -> Vec<Stale> {
+    let limit = |prio: &TaskPriority| {
+        thresholds
+            .iter()
+            .chain(default_thresholds().iter())
+            .find(|t| t.prio == *prio)
+            .map(|t| t.days)
+            .unwrap_or(i64::MAX)
+    };
+    let mut found: Vec<(i64, Stale)> = all
+        .iter()
+        .filter(|t| t.status.is_open())
+        .filter_map(|t| {
+            let since = if since_created { t.created } else { t.modified };
+            let days = (now - since).num_days();
+            let limit = limit(&t.prio);
+            if days <= limit {
+                return None;
+            }
+            Some((
+                days.saturating_sub(limit),
+                Stale {
+                    task: t.clone(),
+                    days,
+                    suggestion: suggest(&t.prio, days, limit),
+                },
+            ))
+        })
+        .collect();
+    found.sort_by_key(|(overdue, _)| Reverse(*overdue));
+    found.into_iter().map(|(_, s)| s).collect()
+}
+
+/// Watched tasks have likely become pressing, low priorities rot until archived, and
+/// anything else which waits that long probably wasn't that important.
+fn suggest(prio: &TaskPriority, days: i64, limit: i64) -> Action {
+    match prio {
+        TaskPriority::Watch => Action::Promote,
+        TaskPriority::SomeDay => Action::Archive,
+        TaskPriority::NiceToDo if days > limit.saturating_mul(2) => Action::Archive,
+        _ => Action::Demote,
+    }
+}
+
+/// `task` after applying `action`, or None if there is nothing to change.
+pub fn apply(task: &Task, action: Action, now: Timestamp) -> Option<Task> {
+    let mut task = task.clone();
+    match action {
+        Action::Promote | Action::Demote => {
+            let prios: Vec<TaskPriority> = TaskPriority::iter().collect();
+            let pos = prios.iter().position(|p| *p == task.prio)?;
+            let pos = match action {
+                Action::Promote => pos.checked_sub(1)?,
+                _ => pos + 1,
+            };
+            task.prio = prios.get(pos)?.clone();
+            // the manual order belongs to the old priority
+            task.rank = None;
+        }
+        Action::Archive => task.status = TaskStatus::Archived,
+        Action::Drop => task.status = TaskStatus::WontDo,
+        Action::Keep => {}
+    }
+    task.modified = now;
+    Some(task)
+}
+
+/// What a weekly review session did.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub kept: usize,
+    pub skipped: usize,
+    /// one line per changed task
+    pub changes: Vec<String>,
+}
+
+/// The priorities in the order the weekly review walks through them: the ones most easily
+/// forgotten about first, then the rest from urgent to nice to do.
+pub fn weekly_buckets() -> Vec<TaskPriority> {
+    let first = [TaskPriority::Watch, TaskPriority::SomeDay];
+    first
+        .iter()
+        .cloned()
+        .chain(TaskPriority::iter().filter(|p| !first.contains(p)))
+        .collect()
+}
+
+impl Summary {
+    pub fn changed(&mut self, task: &Task, change: String) {
+        self.changes.push(format!(
+            "#{} {}: {}",
+            task.id.unwrap_or_default(),
+            task.short,
+            change
+        ));
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Reviewed {} tasks: {} kept, {} skipped, {} changed",
+            self.kept + self.skipped + self.changes.len(),
+            self.kept,
+            self.skipped,
+            self.changes.len()
+        )?;
+        for change in &self.changes {
+            writeln!(f, "  {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Promote => write!(f, "Promote"),
+            Self::Demote => write!(f, "Demote"),
+            Self::Archive => write!(f, "Archive"),
+            Self::Drop => write!(f, "Drop"),
+            Self::Keep => write!(f, "Keep"),
+        }
+    }
+}
+
+impl Display for Threshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.prio.to_string(), self.days)
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, days) = s
+            .split_once('=')
+            .ok_or_else(|| Error::InvalidInput(format!("expected <priority>=<days>, got {}", s)))?;
+        let prio = filter::parse_priority(name)?;
+        // a whole number of days, not negative and small enough to count with
+        let days = days
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| Error::InvalidInput(format!("invalid number of days {}: {}", days, e)))?;
+        Ok(Self {
+            prio,
+            days: days.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn thresholds_and_suggestions() {
+        let now = Utc::now();
+        let threshold: Threshold = "some-day=10".parse().unwrap();
+        assert_eq!(threshold.prio, TaskPriority::SomeDay);
+        assert_eq!(
+            threshold.to_string().parse::<Threshold>().unwrap(),
+            threshold
+        );
+        assert!("often=3".parse::<Threshold>().is_err());
+        for days in ["-5", "-5000000000000000000", "99999999999999999999"] {
+            assert!(matches!(
+                format!("nice-to-do={}", days).parse::<Threshold>(),
+                Err(Error::InvalidInput(_))
+            ));
+        }
+        let largest: Threshold = format!("nice-to-do={}", u32::MAX).parse().unwrap();
+        assert_eq!(suggest(&largest.prio, i64::MAX, i64::MAX), Action::Demote);
+
+        let mut tasks = vec![];
+        for (name, prio, age) in [
+            ("fresh", TaskPriority::SomeDay, 5),
+            ("rotten", TaskPriority::SomeDay, 40),
+            ("watched", TaskPriority::Watch, 20),
+            ("nice", TaskPriority::NiceToDo, 70),
+        ] {
+            let mut t = Task::new(name.to_string(), "".to_string(), prio);
+            t.created = now - Duration::days(age);
+            t.modified = t.created;
+            tasks.push(t);
+        }
+
+        let found = stale(&tasks, &[threshold], false, now);
+        let names: Vec<(&str, Action)> = found
+            .iter()
+            .map(|s| (s.task.short.as_str(), s.suggestion))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("rotten", Action::Archive),
+                ("nice", Action::Demote),
+                ("watched", Action::Promote)
+            ]
+        );
+
+        let promoted = apply(&tasks[2], Action::Promote, now).unwrap();
+        assert_eq!(promoted.prio, TaskPriority::ToDo);
+        assert!(apply(&tasks[0], Action::Demote, now).is_none());
+
+        let buckets = weekly_buckets();
+        assert_eq!(
+            buckets[..3],
+            [
+                TaskPriority::Watch,
+                TaskPriority::SomeDay,
+                TaskPriority::UrgentAndImportant
+            ]
+        );
+        assert_eq!(buckets.len(), TaskPriority::iter().count());
+    }
+}
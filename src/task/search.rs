@@ -0,0 +1,270 @@
+use std::fmt::Display;
+
+use crate::task::Task;
+
+/// Put around matches in names and snippets, shown in bold by the terminal.
+pub const MARK_START: &str = "\x1b[1m";
+pub const MARK_END: &str = "\x1b[0m";
+/// Words of the description shown around the first match.
+pub const SNIPPET_WORDS: usize = 12;
+
+/// A task found by a search, best matches have the lowest rank.
+#[derive(Debug)]
+pub struct Hit {
+    pub task: Task,
+    pub rank: f64,
+    /// the name with the matches marked
+    pub name: String,
+    /// part of the description around a match, if the description matched
+    pub snippet: Option<String>,
+}
+
+/// One term of a query: a word, a "quoted phrase", either ending in `*` to match as a prefix.
+#[derive(Debug, PartialEq)]
+struct Pattern {
+    words: Vec<String>,
+    prefix: bool,
+}
+
+/// The words of `text`, lowercase, with their byte ranges in `text`.
+fn words(text: &str) -> Vec<(usize, usize, String)> {
+    let mut words = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                words.push((s, i, text[s..i].to_lowercase()));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    words
+}
+
+fn parse(query: &str) -> Vec<Pattern> {
+    let mut patterns = vec![];
+    let mut rest = query.trim();
+    while !rest.is_empty() {
+        let (term, tail) = match rest.strip_prefix('"') {
+            Some(phrase) => match phrase.find('"') {
+                Some(end) => (&phrase[..end], &phrase[end + 1..]),
+                None => (phrase, ""),
+            },
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+        let prefix = tail.starts_with('*') || term.ends_with('*');
+        let tail = tail.strip_prefix('*').unwrap_or(tail);
+        let words: Vec<String> = words(term).into_iter().map(|(_, _, w)| w).collect();
+        // the operators FTS5 understands, everything has to match here anyway
+        if !words.is_empty() && !matches!(term, "AND" | "OR" | "NOT") {
+            patterns.push(Pattern { words, prefix });
+        }
+        rest = tail.trim_start();
+    }
+    patterns
+}
+
+/// `query` in the FTS5 query syntax: every term quoted, so nothing the user types is read
+/// as an operator or a syntax error. None if there is nothing to search for.
+pub fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = parse(query)
+        .into_iter()
+        .map(|p| {
+            format!(
+                "\"{}\"{}",
+                p.words.join(" "),
+                if p.prefix { "*" } else { "" }
+            )
+        })
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Byte ranges where `pattern` occurs in `text`, given as its `words`.
+fn find(pattern: &Pattern, words: &[(usize, usize, String)]) -> Vec<(usize, usize)> {
+    let n = pattern.words.len();
+    if n == 0 || words.len() < n {
+        return vec![];
+    }
+    words
+        .windows(n)
+        .filter(|window| {
+            window
+                .iter()
+                .zip(&pattern.words)
+                .enumerate()
+                .all(|(i, (w, p))| match pattern.prefix && i == n - 1 {
+                    true => w.2.starts_with(p.as_str()),
+                    false => w.2 == *p,
+                })
+        })
+        .map(|window| (window[0].0, window[n - 1].1))
+        .collect()
+}
+
+/// `text` with the byte `ranges` marked, ranges are sorted and don't overlap.
+fn highlight(text: &str, ranges: &[(usize, usize)]) -> String {
+    let mut out = String::new();
+    let mut at = 0;
+    for &(start, end) in ranges {
+        if start < at {
+            continue;
+        }
+        out += &text[at..start];
+        out += MARK_START;
+        out += &text[start..end];
+        out += MARK_END;
+        at = end;
+    }
+    out += &text[at..];
+    out
+}
+
+/// Searches the tasks in memory, for datasources without a search index. Supports the
+/// same "phrase" and prefix* terms, all of which have to match the name or description.
+pub fn search(all: &[Task], query: &str) -> Vec<Hit> {
+    let patterns = parse(query);
+    if patterns.is_empty() {
+        return vec![];
+    }
+    let mut hits: Vec<Hit> = all
+        .iter()
+        .filter_map(|task| {
+            let name_words = words(&task.short);
+            let desc_words = words(&task.desc);
+            let mut in_name = vec![];
+            let mut in_desc = vec![];
+            for p in &patterns {
+                let (name, desc) = (find(p, &name_words), find(p, &desc_words));
+                if name.is_empty() && desc.is_empty() {
+                    return None;
+                }
+                in_name.extend(name);
+                in_desc.extend(desc);
+            }
+            in_name.sort();
+            in_desc.sort();
+            let snippet = in_desc.first().map(|&(first, _)| {
+                let at = desc_words.iter().position(|w| w.0 == first).unwrap_or(0);
+                let from = at.saturating_sub(SNIPPET_WORDS / 2);
+                let to = (from + SNIPPET_WORDS).min(desc_words.len());
+                let (start, end) = (desc_words[from].0, desc_words[to - 1].1);
+                let ranges: Vec<(usize, usize)> = in_desc
+                    .iter()
+                    .filter(|(s, e)| *s >= start && *e <= end)
+                    .map(|(s, e)| (s - start, e - start))
+                    .collect();
+                format!(
+                    "{}{}{}",
+                    if from > 0 { "…" } else { "" },
+                    highlight(&task.desc[start..end], &ranges),
+                    if to < desc_words.len() { "…" } else { "" }
+                )
+            });
+            Some(Hit {
+                task: task.clone(),
+                // like bm25, lower is better, matches in the name count more
+                rank: -((in_name.len() * 10 + in_desc.len()) as f64),
+                name: highlight(&task.short, &in_name),
+                snippet,
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| a.rank.total_cmp(&b.rank));
+    hits
+}
+
+impl Hit {
+    /// Removes the marks around matches, for output which isn't a terminal.
+    pub fn strip_marks(&mut self) {
+        self.name = unmark(&self.name);
+        self.snippet = self.snippet.as_deref().map(unmark);
+    }
+}
+
+fn unmark(s: &str) -> String {
+    s.replace(MARK_START, "").replace(MARK_END, "")
+}
+
+impl Display for Hit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "#{:<4} {:<22} {:<10} {}",
+            self.task.id.unwrap_or_default(),
+            self.task.prio.to_string(),
+            self.task.status.to_string(),
+            self.name
+        )?;
+        if let Some(snippet) = &self.snippet {
+            writeln!(f, "      {}", snippet.replace('\n', " "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskPriority;
+
+    #[test]
+    fn in_memory() {
+        assert_eq!(
+            parse("\"release notes\" deploy* AND x"),
+            vec![
+                Pattern {
+                    words: vec!["release".to_string(), "notes".to_string()],
+                    prefix: false
+                },
+                Pattern {
+                    words: vec!["deploy".to_string()],
+                    prefix: true
+                },
+                Pattern {
+                    words: vec!["x".to_string()],
+                    prefix: false
+                },
+            ]
+        );
+
+        let notes = Task::new(
+            "Write release notes".to_string(),
+            "".to_string(),
+            TaskPriority::ToDo,
+        );
+        let deploy = Task::new(
+            "Deployment".to_string(),
+            "Check the release, then write the notes".to_string(),
+            TaskPriority::ToDo,
+        );
+        let all = vec![notes, deploy];
+
+        let hits = search(&all, "\"release notes\"");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].name,
+            format!("Write {}release notes{}", MARK_START, MARK_END)
+        );
+
+        let hits = search(&all, "release notes");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].task.short, "Write release notes");
+        assert!(hits[1].snippet.as_ref().unwrap().contains(MARK_START));
+
+        assert_eq!(search(&all, "deploy").len(), 0);
+        assert_eq!(search(&all, "deploy*").len(), 1);
+
+        let mut hit = search(&all, "release").remove(0);
+        hit.strip_marks();
+        assert_eq!(hit.name, "Write release notes");
+
+        assert_eq!(
+            fts_query("\"release notes\" deploy* foo\"").as_deref(),
+            Some("\"release notes\" \"deploy\"* \"foo\"")
+        );
+        assert_eq!(fts_query("AND \""), None);
+    }
+}
@@ -0,0 +1,211 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::task::{Task, TaskPriority, TaskStatus};
+
+/// A `Task` field tasks can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum SortField {
+    Id,
+    Name,
+    Status,
+    Prio,
+    Created,
+    Started,
+    Modified,
+    Rank,
+}
+
+/// Sorts by `field`, in descending order if `descending` is set. Tasks missing the field
+/// (never started, unranked) come last in ascending order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+/// The order `list` uses when no sort keys are given: by priority, then the manual rank.
+pub const DEFAULT_ORDER: [SortKey; 2] = [
+    SortKey {
+        field: SortField::Prio,
+        descending: false,
+    },
+    SortKey {
+        field: SortField::Rank,
+        descending: false,
+    },
+];
+
+impl SortField {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Name => "name",
+            Self::Status => "status",
+            Self::Prio => "prio",
+            Self::Created => "created",
+            Self::Started => "started",
+            Self::Modified => "modified",
+            Self::Rank => "rank",
+        }
+    }
+
+    fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        match self {
+            Self::Id => a.id.cmp(&b.id),
+            Self::Name => a.short.cmp(&b.short),
+            Self::Status => a.status.cmp(&b.status),
+            Self::Prio => a.prio.cmp(&b.prio),
+            Self::Created => a.created.cmp(&b.created),
+            Self::Started => missing_last(&a.started, &b.started),
+            Self::Modified => a.modified.cmp(&b.modified),
+            Self::Rank => missing_last(&a.rank, &b.rank),
+        }
+    }
+
+    /// The SQL expression sorting like `compare`, over the columns of the tasks table.
+    pub fn sql(&self) -> String {
+        match self {
+            Self::Id => "rowid".to_string(),
+            Self::Name => "short".to_string(),
+            Self::Status => case(
+                "status",
+                TaskStatus::iter().map(|s| (s.to_string(), s.get_ordering_value())),
+            ),
+            Self::Prio => case(
+                "prio",
+                TaskPriority::iter().map(|p| (p.to_string(), p.get_ordering_value())),
+            ),
+            Self::Created => "created".to_string(),
+            Self::Started => "started".to_string(),
+            Self::Modified => "modified".to_string(),
+            Self::Rank => "rank".to_string(),
+        }
+    }
+}
+
+fn missing_last<T: Ord>(a: &Option<T>, b: &Option<T>) -> Ordering {
+    a.is_none().cmp(&b.is_none()).then(a.cmp(b))
+}
+
+fn case(column: &str, values: impl Iterator<Item = (String, u8)>) -> String {
+    let whens: Vec<String> = values
+        .map(|(name, value)| format!("WHEN '{}' THEN {}", name, value))
+        .collect();
+    format!("CASE {} {} END", column, whens.join(" "))
+}
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.descending {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.field.name())
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (descending, name) = match s.trim().strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, s.trim()),
+        };
+        let field = match name {
+            "short" => SortField::Name,
+            "priority" => SortField::Prio,
+            _ => match SortField::iter().find(|f| f.name() == name) {
+                Some(field) => field,
+                None => {
+                    let names: Vec<&str> = SortField::iter().map(|f| f.name()).collect();
+                    return Err(format!(
+                        "unknown sort key {}, use one of {}",
+                        name,
+                        names.join(", ")
+                    ));
+                }
+            },
+        };
+        Ok(Self { field, descending })
+    }
+}
+
+/// Compares two tasks by each key in turn.
+pub fn compare(a: &Task, b: &Task, keys: &[SortKey]) -> Ordering {
+    keys.iter()
+        .map(|k| {
+            let ord = k.field.compare(a, b);
+            if k.descending {
+                ord.reverse()
+            } else {
+                ord
+            }
+        })
+        .find(|ord| ord.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Stable sort of `tasks` by `keys`.
+pub fn sort(tasks: &mut [Task], keys: &[SortKey]) {
+    tasks.sort_by(|a, b| compare(a, b, keys))
+}
+
+/// The ORDER BY clause for `keys`, empty if there are none.
+pub fn order_by(keys: &[SortKey]) -> String {
+    if keys.is_empty() {
+        return String::new();
+    }
+    let terms: Vec<String> = keys
+        .iter()
+        .map(|k| match (k.field, k.descending) {
+            (SortField::Started | SortField::Rank, false) => {
+                format!("{} ASC NULLS LAST", k.field.sql())
+            }
+            (SortField::Started | SortField::Rank, true) => {
+                format!("{} DESC NULLS FIRST", k.field.sql())
+            }
+            (_, false) => format!("{} ASC", k.field.sql()),
+            (_, true) => format!("{} DESC", k.field.sql()),
+        })
+        .collect();
+    format!(" ORDER BY {}", terms.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_compare() {
+        let keys: Vec<SortKey> = "prio,status,-created,name"
+            .split(',')
+            .map(|k| k.parse().unwrap())
+            .collect();
+        assert_eq!(keys[2].to_string(), "-created");
+        assert!("color".parse::<SortKey>().is_err());
+
+        let mut a = Task::new("a".to_string(), "".to_string(), TaskPriority::ToDo);
+        let mut b = Task::new("b".to_string(), "".to_string(), TaskPriority::ToDo);
+        b.created = a.created;
+        assert_eq!(compare(&a, &b, &keys), Ordering::Less);
+        a.status = TaskStatus::Completed;
+        b.status = TaskStatus::Started;
+        assert_eq!(compare(&a, &b, &keys), Ordering::Greater);
+        b.prio = TaskPriority::SomeDay;
+        assert_eq!(compare(&a, &b, &keys), Ordering::Less);
+
+        assert_eq!(
+            TaskStatus::Started.cmp(&TaskStatus::Created),
+            Ordering::Less
+        );
+        assert_eq!(
+            order_by(&keys[2..]),
+            " ORDER BY created DESC, short ASC".to_string()
+        );
+    }
+}
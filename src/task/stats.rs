@@ -0,0 +1,280 @@
+use std::fmt::Display;
+
+use chrono::{Datelike, Duration, Local};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::task::{Task, TaskPriority, TaskStatus, Timestamp};
+
+/// Throughput and lead time figures over all tasks.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub open_by_priority: Vec<Count>,
+    pub open_by_status: Vec<Count>,
+    /// oldest week first
+    pub weeks: Vec<Week>,
+    /// created to completed
+    pub median_lead_time_days: Option<f64>,
+    /// started to completed
+    pub median_cycle_time_days: Option<f64>,
+    pub completed: usize,
+    /// share of completed tasks which were urgent, 0..1
+    pub urgent_share: Option<f64>,
+    /// share of completed tasks which were important, 0..1
+    pub important_share: Option<f64>,
+    /// tasks per status at the end of each week, oldest week first
+    pub flow: Vec<Flow>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Week {
+    /// ISO week, e.g. 2026-W42
+    pub week: String,
+    pub created: usize,
+    pub completed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Flow {
+    pub week: String,
+    pub open: usize,
+    pub statuses: Vec<Count>,
+}
+
+/// When a completed task was completed. Tasks completed before this was recorded use the
+/// time of their last change.
+fn completed_at(task: &Task) -> Option<Timestamp> {
+    match task.status {
+        TaskStatus::Completed => Some(task.completed.unwrap_or(task.modified)),
+        _ => None,
+    }
+}
+
+/// The status `task` had at `t`, as far as its timestamps tell. None if it didn't exist yet.
+fn status_at(task: &Task, t: Timestamp) -> Option<TaskStatus> {
+    if task.created > t {
+        return None;
+    }
+    let closed = match task.status {
+        TaskStatus::Created | TaskStatus::Started => None,
+        TaskStatus::Completed => completed_at(task),
+        _ => Some(task.modified),
+    };
+    match (closed, task.started) {
+        (Some(closed), _) if closed <= t => Some(task.status.clone()),
+        (_, Some(started)) if started <= t => Some(TaskStatus::Started),
+        _ => Some(TaskStatus::Created),
+    }
+}
+
+fn week_of(t: Timestamp) -> String {
+    let week = t.with_timezone(&Local).iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+fn median(mut values: Vec<Duration>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort();
+    let mid = values.len() / 2;
+    let median = match values.len() % 2 {
+        0 => (values[mid - 1] + values[mid]) / 2,
+        _ => values[mid],
+    };
+    Some(median.num_minutes() as f64 / (24.0 * 60.0))
+}
+
+/// Computes the statistics, with created and completed counts for the last `weeks` weeks.
+pub fn stats(all: &[Task], weeks: usize, now: Timestamp) -> Stats {
+    let open: Vec<&Task> = all.iter().filter(|t| t.status.is_open()).collect();
+    let open_by_priority = TaskPriority::iter()
+        .map(|p| Count {
+            name: p.to_string(),
+            count: open.iter().filter(|t| t.prio == p).count(),
+        })
+        .collect();
+    let open_by_status = TaskStatus::iter()
+        .filter(|s| s.is_open())
+        .map(|s| Count {
+            name: s.to_string(),
+            count: open.iter().filter(|t| t.status == s).count(),
+        })
+        .collect();
+
+    let weeks: Vec<Week> = (0..weeks)
+        .rev()
+        .map(|ago| {
+            let week = week_of(now - Duration::weeks(ago as i64));
+            Week {
+                created: all.iter().filter(|t| week_of(t.created) == week).count(),
+                completed: all
+                    .iter()
+                    .filter(|t| completed_at(t).is_some_and(|c| week_of(c) == week))
+                    .count(),
+                week,
+            }
+        })
+        .collect();
+
+    let flow = (0..weeks.len())
+        .rev()
+        .zip(weeks.iter())
+        .map(|(ago, week)| {
+            let at = now - Duration::weeks(ago as i64);
+            let statuses: Vec<Count> = TaskStatus::iter()
+                .map(|s| Count {
+                    name: s.to_string(),
+                    count: all
+                        .iter()
+                        .filter(|t| status_at(t, at).as_ref() == Some(&s))
+                        .count(),
+                })
+                .collect();
+            Flow {
+                week: week.week.clone(),
+                open: all
+                    .iter()
+                    .filter(|t| status_at(t, at).is_some_and(|s| s.is_open()))
+                    .count(),
+                statuses,
+            }
+        })
+        .collect();
+
+    let completed: Vec<(&Task, Timestamp)> = all
+        .iter()
+        .filter_map(|t| completed_at(t).map(|c| (t, c)))
+        .collect();
+    let share = |pred: fn(&TaskPriority) -> bool| match completed.len() {
+        0 => None,
+        n => Some(completed.iter().filter(|(t, _)| pred(&t.prio)).count() as f64 / n as f64),
+    };
+
+    Stats {
+        open_by_priority,
+        open_by_status,
+        weeks,
+        median_lead_time_days: median(completed.iter().map(|(t, c)| *c - t.created).collect()),
+        median_cycle_time_days: median(
+            completed
+                .iter()
+                .filter_map(|(t, c)| t.started.map(|s| *c - s))
+                .collect(),
+        ),
+        completed: completed.len(),
+        urgent_share: share(|p| {
+            matches!(
+                p,
+                TaskPriority::UrgentAndImportant | TaskPriority::UrgentNotImportant
+            )
+        }),
+        important_share: share(|p| {
+            matches!(
+                p,
+                TaskPriority::UrgentAndImportant | TaskPriority::ImportantNotUrgent
+            )
+        }),
+        flow,
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let days = |d: Option<f64>| match d {
+            Some(d) => format!("{:.1} days", d),
+            None => "-".to_string(),
+        };
+        let percent = |s: Option<f64>| match s {
+            Some(s) => format!("{:.0}%", s * 100.0),
+            None => "-".to_string(),
+        };
+        writeln!(f, "Open tasks by priority:")?;
+        for c in &self.open_by_priority {
+            writeln!(f, "  {:<25} {:>5}", c.name, c.count)?;
+        }
+        writeln!(f, "Open tasks by status:")?;
+        for c in &self.open_by_status {
+            writeln!(f, "  {:<25} {:>5}", c.name, c.count)?;
+        }
+        writeln!(f, "Week        created  completed")?;
+        for w in &self.weeks {
+            writeln!(f, "  {:<10} {:>7} {:>10}", w.week, w.created, w.completed)?;
+        }
+        writeln!(
+            f,
+            "Median lead time: {}, cycle time: {}",
+            days(self.median_lead_time_days),
+            days(self.median_cycle_time_days)
+        )?;
+        writeln!(
+            f,
+            "Of {} completed tasks {} were urgent and {} important",
+            self.completed,
+            percent(self.urgent_share),
+            percent(self.important_share)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn throughput() {
+        let now = Utc::now();
+        let mut tasks = vec![];
+        for (prio, status, age, took) in [
+            (
+                TaskPriority::UrgentAndImportant,
+                TaskStatus::Completed,
+                10,
+                2,
+            ),
+            (
+                TaskPriority::ImportantNotUrgent,
+                TaskStatus::Completed,
+                9,
+                4,
+            ),
+            (TaskPriority::SomeDay, TaskStatus::Completed, 3, 1),
+            (TaskPriority::SomeDay, TaskStatus::Created, 1, 0),
+            (TaskPriority::ToDo, TaskStatus::Started, 0, 0),
+        ] {
+            let mut t = Task::new("t".to_string(), "".to_string(), prio);
+            t.status = status;
+            t.created = now - Duration::days(age);
+            if t.status == TaskStatus::Completed {
+                t.started = Some(t.created + Duration::days(1));
+                t.completed = Some(t.created + Duration::days(took));
+            }
+            tasks.push(t);
+        }
+
+        let stats = stats(&tasks, 4, now);
+        assert_eq!(stats.open_by_priority[3].count, 1);
+        assert_eq!(stats.open_by_priority[6].count, 1);
+        assert_eq!(stats.open_by_status[0].count, 1);
+        assert_eq!(stats.weeks.len(), 4);
+        assert_eq!(stats.weeks[3].week, week_of(now));
+        assert_eq!(stats.weeks.iter().map(|w| w.created).sum::<usize>(), 5);
+        assert_eq!(stats.median_lead_time_days, Some(2.0));
+        assert_eq!(stats.median_cycle_time_days, Some(1.0));
+        assert_eq!(stats.important_share, Some(2.0 / 3.0));
+        assert_eq!(stats.urgent_share, Some(1.0 / 3.0));
+        // a week ago the first task was done and the second one started
+        assert_eq!(stats.flow[1].open, 0);
+        assert_eq!(stats.flow[2].open, 1);
+        assert_eq!(stats.flow[2].statuses[1].count, 1);
+        assert_eq!(stats.flow[3].open, 2);
+        assert_eq!(stats.flow[3].statuses[2].count, 3);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::task::{Task, TaskPriority, Timestamp};
+
+/// A stretch of time worked on a task, still running if it has no `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub task: Uuid,
+    pub start: Timestamp,
+    pub end: Option<Timestamp>,
+}
+
+impl Session {
+    /// The part of the session between `from` and `to`, running sessions count until `to`.
+    pub fn within(&self, from: Timestamp, to: Timestamp) -> Duration {
+        let start = self.start.max(from);
+        let end = self.end.unwrap_or(to).min(to);
+        (end - start).max(Duration::zero())
+    }
+}
+
+/// Time worked between two points in time, per task, per group and per priority.
+#[derive(Debug, Default)]
+pub struct Timesheet {
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+    pub tasks: Vec<(Task, Duration)>,
+    /// a group is the top level task with all its subtasks
+    pub groups: Vec<(String, Duration)>,
+    pub priorities: Vec<(TaskPriority, Duration)>,
+    pub total: Duration,
+}
+
+/// Total time worked on `task` up to `now`.
+pub fn spent(task: &Task, sessions: &[Session], now: Timestamp) -> Duration {
+    sessions
+        .iter()
+        .filter(|s| s.task == task.uuid)
+        .map(|s| s.within(s.start, now))
+        .fold(Duration::zero(), |a, b| a + b)
+}
+
+/// Adds up the sessions between `from` and `to`, most time spent first.
+pub fn timesheet(all: &[Task], sessions: &[Session], from: Timestamp, to: Timestamp) -> Timesheet {
+    let mut per_task: BTreeMap<Uuid, Duration> = BTreeMap::new();
+    for s in sessions {
+        let d = s.within(from, to);
+        if d > Duration::zero() {
+            *per_task.entry(s.task).or_insert(Duration::zero()) += d;
+        }
+    }
+
+    let mut sheet = Timesheet {
+        from: Some(from),
+        to: Some(to),
+        ..Default::default()
+    };
+    let mut groups: BTreeMap<String, Duration> = BTreeMap::new();
+    let mut priorities: BTreeMap<TaskPriority, Duration> = BTreeMap::new();
+    for (uuid, d) in per_task {
+        let task = match all.iter().find(|t| t.uuid == uuid) {
+            Some(task) => task,
+            None => continue,
+        };
+        *groups
+            .entry(root(all, task).short.clone())
+            .or_insert(Duration::zero()) += d;
+        *priorities
+            .entry(task.prio.clone())
+            .or_insert(Duration::zero()) += d;
+        sheet.tasks.push((task.clone(), d));
+        sheet.total += d;
+    }
+    sheet.tasks.sort_by_key(|(_, d)| -*d);
+    sheet.groups = groups.into_iter().collect();
+    sheet.groups.sort_by_key(|(_, d)| -*d);
+    sheet.priorities = priorities.into_iter().collect();
+    sheet
+}
+
+/// The top level task `task` is a subtask of, or `task` itself.
+pub(crate) fn root<'a>(all: &'a [Task], task: &'a Task) -> &'a Task {
+    let mut current = task;
+    // bounded, in case parents loop
+    for _ in 0..all.len() {
+        match current
+            .parent
+            .and_then(|p| all.iter().find(|t| t.uuid == p))
+        {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    current
+}
+
+/// `1h 05m`, or `12m` for less than an hour.
+pub fn format_duration(d: Duration) -> String {
+    let minutes = d.num_minutes();
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {:02}m", hours, minutes % 60),
+    }
+}
+
+impl Display for Timesheet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            writeln!(
+                f,
+                "Timesheet {} - {}",
+                from.format("%Y-%m-%d"),
+                to.format("%Y-%m-%d")
+            )?;
+        }
+        writeln!(f, "By task:")?;
+        for (task, d) in &self.tasks {
+            writeln!(
+                f,
+                "  {:>8}  #{} {}",
+                format_duration(*d),
+                task.id.unwrap_or_default(),
+                task.short
+            )?;
+        }
+        writeln!(f, "By group:")?;
+        for (group, d) in &self.groups {
+            writeln!(f, "  {:>8}  {}", format_duration(*d), group)?;
+        }
+        writeln!(f, "By priority:")?;
+        for (prio, d) in &self.priorities {
+            writeln!(f, "  {:>8}  {}", format_duration(*d), prio.to_string())?;
+        }
+        writeln!(f, "Total: {}", format_duration(self.total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn sheet() {
+        let now = Utc::now();
+        let release = Task::new("release".to_string(), "".to_string(), TaskPriority::ToDo);
+        let mut docs = Task::new("docs".to_string(), "".to_string(), TaskPriority::Watch);
+        docs.parent = Some(release.uuid);
+        let all = vec![release.clone(), docs.clone()];
+        let sessions = vec![
+            Session {
+                task: release.uuid,
+                start: now - Duration::days(10),
+                end: Some(now - Duration::days(10) + Duration::hours(5)),
+            },
+            Session {
+                task: release.uuid,
+                start: now - Duration::hours(3),
+                end: Some(now - Duration::hours(2)),
+            },
+            Session {
+                task: docs.uuid,
+                start: now - Duration::minutes(90),
+                end: None,
+            },
+        ];
+
+        assert_eq!(spent(&release, &sessions, now), Duration::hours(6));
+        let sheet = timesheet(&all, &sessions, now - Duration::days(1), now);
+        assert_eq!(sheet.total, Duration::minutes(150));
+        assert_eq!(sheet.tasks[0].0.short, "docs");
+        assert_eq!(
+            sheet.groups,
+            vec![("release".to_string(), Duration::minutes(150))]
+        );
+        assert_eq!(sheet.priorities[0].0, TaskPriority::ToDo);
+        assert_eq!(format_duration(Duration::minutes(65)), "1h 05m");
+    }
+}